@@ -1,64 +1,149 @@
 use super::client;
+use super::client::Failure;
 use aws::xml::{AssumeRoleResponse, AssumeRoleResult, Credentials};
-use reqwest::blocking::Response;
+use logging;
+use secret::Secret;
 use serde_xml_rs;
 use std::error::Error;
-use std::io;
+use std::fmt;
 
+/// Why a role could not be assumed
+#[derive(Debug)]
+pub enum AssumeRoleError {
+    /// The HTTP client could not be set up, e.g. because of invalid TLS settings
+    Client(String),
+    /// The request could not be sent or the response could not be read
+    Http(reqwest::Error),
+    /// STS responded with an error status
+    Rejected(reqwest::StatusCode),
+    /// The response of STS contains no credentials
+    InvalidResponse(String),
+}
+
+impl fmt::Display for AssumeRoleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            AssumeRoleError::Client(ref msg) => write!(f, "{}", msg),
+            AssumeRoleError::Http(ref e) => write!(f, "{}", e),
+            AssumeRoleError::Rejected(status) => write!(f, "sts assume role returned {}", status),
+            AssumeRoleError::InvalidResponse(ref msg) => {
+                write!(f, "sts assume role returned an invalid response: {}", msg)
+            }
+        }
+    }
+}
+
+impl Error for AssumeRoleError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            AssumeRoleError::Http(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// Assumes the role with the SAML assertion and returns the temporary credentials
 pub fn assume_role(
     arn: &str,
     principal: &str,
-    saml_assertion: &str,
+    saml_assertion: &Secret,
     session_duration: Option<i64>,
     sts_endpoint: Option<&str>,
-) -> Result<Credentials, impl Error> {
-    let res: Response = match client::get_proxied_client_builder()
-        .build()
-        .unwrap()
+) -> Result<Credentials, AssumeRoleError> {
+    let _span = logging::span("sts");
+    debug!("assuming role {}", arn);
+
+    let client = client::get_proxied_client_builder()
+        .and_then(|cb| cb.build().map_err(|e| e.to_string()))
+        .map_err(AssumeRoleError::Client)?;
+
+    let request = client
         .post(sts_endpoint.unwrap_or("https://sts.amazonaws.com/"))
         .query(&[("Version", "2011-06-15"), ("Action", "AssumeRoleWithSAML")])
         .form(&[
             ("PrincipalArn", principal),
             ("RoleArn", arn),
-            ("SAMLAssertion", saml_assertion),
+            ("SAMLAssertion", saml_assertion.expose()),
             (
                 "DurationSeconds",
                 &format!("{}", session_duration.or(Some(3600)).unwrap()),
             ),
-        ])
-        .send()
-    {
-        Ok(r) => r,
-        Err(e) => {
-            return Err(io::Error::new(io::ErrorKind::Other, e));
-        }
-    };
+        ]);
+
+    let text = client::retry(|| {
+        let res = request
+            .try_clone()
+            .unwrap()
+            .send()
+            .map_err(|e| client::classify(e, false).map(AssumeRoleError::Http))?;
 
-    if res.status() != 200 {
         let status = res.status();
-        println!("response: '{:?}'", res.text());
-        return Err(io::Error::new(
-            io::ErrorKind::Other,
-            format!("sts assume role returned {}", status),
-        ));
-    }
+        // The response contains the credentials
+        let text = Secret::from(
+            res.text()
+                .map_err(|e| Failure::Permanent(AssumeRoleError::Http(e)))?,
+        );
+
+        if status == 200 {
+            return Ok(text);
+        }
+
+        let e = AssumeRoleError::Rejected(status);
 
-    let text = res.text().unwrap();
+        // STS responds with 400 if requests are throttled
+        if client::is_transient_status(status) || text.expose().contains("<Code>Throttling</Code>")
+        {
+            Err(Failure::Transient(e))
+        } else {
+            error!("sts assume role returned {}: {}", status, text.expose());
+            Err(Failure::Permanent(e))
+        }
+    })?;
+
+    parse_response(text.expose())
+}
 
-    let response: AssumeRoleResponse = serde_xml_rs::from_str(&text).unwrap();
+/// Returns the credentials of an AssumeRoleWithSAML response
+fn parse_response(text: &str) -> Result<Credentials, AssumeRoleError> {
+    let response: AssumeRoleResponse = serde_xml_rs::from_str(text)
+        .map_err(|e| AssumeRoleError::InvalidResponse(e.to_string()))?;
 
-    let credentials = (&response.response[0].result)
+    response
+        .response
         .into_iter()
-        .find(|r| match r {
-            AssumeRoleResult::Credentials(_) => true,
-            _ => false,
+        .flat_map(|r| r.result)
+        .find_map(|r| match r {
+            AssumeRoleResult::Credentials(c) => Some(c),
+            _ => None,
         })
-        .unwrap();
+        .ok_or_else(|| AssumeRoleError::InvalidResponse("no credentials".into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    let credentials = match credentials {
-        AssumeRoleResult::Credentials(ref c) => c,
-        _ => panic!("this should never, ever happen. It did. awesome."),
-    };
+    #[test]
+    fn parses_credentials_of_the_response() {
+        let response = "<AssumeRoleWithSAMLResponse><AssumeRoleWithSAMLResult>\
+             <Audience>https://signin.aws.amazon.com/saml</Audience>\
+             <Credentials><AccessKeyId>ASIA</AccessKeyId><SecretAccessKey>secret</SecretAccessKey>\
+             <SessionToken>token</SessionToken><Expiration>2026-10-19T12:00:00Z</Expiration></Credentials>\
+             </AssumeRoleWithSAMLResult></AssumeRoleWithSAMLResponse>";
 
-    Ok(credentials.clone())
+        let credentials = parse_response(response).unwrap();
+
+        assert_eq!(credentials.access_key_id, "ASIA");
+        assert_eq!(credentials.session_token.expose(), "token");
+    }
+
+    #[test]
+    fn rejects_responses_without_credentials() {
+        let response = "<AssumeRoleWithSAMLResponse><AssumeRoleWithSAMLResult>\
+             <Audience>https://signin.aws.amazon.com/saml</Audience>\
+             </AssumeRoleWithSAMLResult></AssumeRoleWithSAMLResponse>";
+
+        assert!(parse_response(response).is_err());
+        assert!(parse_response("<html>").is_err());
+    }
 }
@@ -4,6 +4,8 @@ use std::path::PathBuf;
 use super::dirs;
 use super::ini;
 
+/// Loads the AWS credentials file, `~/.aws/credentials`, and returns it with its path.
+/// The file is created if it does not exist yet.
 pub fn load_credentials_file() -> Result<(ini::Ini, PathBuf), ini::Error> {
     let filename = dirs::home_dir().unwrap().join(".aws");
 
@@ -12,6 +12,7 @@ pub mod credentials;
 pub mod xml;
 
 use saml::parse_assertion;
+use secret::Secret;
 
 #[derive(Debug)]
 pub struct AWSAccountInfo {
@@ -21,7 +22,7 @@ pub struct AWSAccountInfo {
 
 pub fn extract_saml_accounts(
     body: &str,
-    saml_response_b64: &str,
+    saml_response_b64: &Secret,
 ) -> Result<Vec<AWSAccountInfo>, io::Error> {
     trace!("html={:?}", body);
     let doc = Html::parse_document(body);
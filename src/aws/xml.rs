@@ -1,5 +1,7 @@
 #![allow(dead_code)]
 
+use secret::Secret;
+
 #[derive(Debug, Deserialize)]
 #[serde(rename = "Response")]
 pub struct AssumeRoleResponse {
@@ -30,9 +32,9 @@ pub struct Credentials {
     #[serde(rename = "AccessKeyId")]
     pub access_key_id: String,
     #[serde(rename = "SecretAccessKey")]
-    pub secret_access_key: String,
+    pub secret_access_key: Secret,
     #[serde(rename = "SessionToken")]
-    pub session_token: String,
+    pub session_token: Secret,
     #[serde(rename = "Expiration")]
     pub expiration: String,
 }
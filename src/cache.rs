@@ -0,0 +1,175 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use base64::prelude::*;
+use keyring::Entry;
+use openssl::rand::rand_bytes;
+use openssl::symm::{decrypt_aead, encrypt_aead, Cipher};
+use serde_json;
+
+use aws::xml::Credentials;
+use config;
+use secret::Secret;
+
+/// Keyring entry holding the key the cache is encrypted with
+const KEYRING_SERVICE: &str = "saml2aws-auto";
+const KEYRING_USER: &str = "credential-cache-key";
+
+const KEY_LEN: usize = 32;
+const NONCE_LEN: usize = 12;
+const TAG_LEN: usize = 16;
+
+/// Credentials of a profile in the cache, with the region of its account
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedCredentials {
+    pub access_key_id: String,
+    pub secret_access_key: Secret,
+    pub session_token: Secret,
+    pub expiration: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub region: Option<String>,
+}
+
+/// Credentials of groups with encrypted storage, by profile name. The cache is encrypted
+/// with AES-256-GCM using a key stored in the keyring.
+#[derive(Debug, Default)]
+pub struct CredentialCache {
+    path: PathBuf,
+    profiles: BTreeMap<String, CachedCredentials>,
+}
+
+impl CredentialCache {
+    /// Loads the cache stored in the config directory. The keyring is only accessed if
+    /// the cache exists.
+    pub fn load(config_dir: &Path) -> Result<Self, Box<dyn Error>> {
+        let path = config_dir.join("credentials.enc");
+
+        if !path.exists() {
+            return Ok(CredentialCache {
+                path,
+                profiles: BTreeMap::new(),
+            });
+        }
+
+        let data = fs::read(&path)?;
+        let plaintext = Secret::from(String::from_utf8(decrypt(&encryption_key(false)?, &data)?)?);
+
+        Ok(CredentialCache {
+            path,
+            profiles: serde_json::from_str(plaintext.expose())?,
+        })
+    }
+
+    /// Encrypts the cache and writes it, creating the key on the first save
+    pub fn save(&self) -> Result<(), Box<dyn Error>> {
+        let plaintext = Secret::from(serde_json::to_string(&self.profiles)?);
+        let data = encrypt(&encryption_key(true)?, plaintext.expose().as_bytes())?;
+
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        config::write_atomic(self.path.to_str().unwrap(), &data)?;
+        Ok(())
+    }
+
+    /// Returns the credentials of the profile
+    pub fn get(&self, profile: &str) -> Option<&CachedCredentials> {
+        self.profiles.get(profile)
+    }
+
+    /// Removes the credentials of the profile. Returns true if there were any.
+    pub fn remove(&mut self, profile: &str) -> bool {
+        self.profiles.remove(profile).is_some()
+    }
+
+    /// Adds the credentials to the cache, replacing the ones of the profile
+    pub fn insert(&mut self, profile: &str, credentials: &Credentials, region: Option<&str>) {
+        self.profiles.insert(
+            profile.into(),
+            CachedCredentials {
+                access_key_id: credentials.access_key_id.clone(),
+                secret_access_key: credentials.secret_access_key.clone(),
+                session_token: credentials.session_token.clone(),
+                expiration: credentials.expiration.clone(),
+                region: region.map(String::from),
+            },
+        );
+    }
+}
+
+/// Returns the key of the cache. A new key is generated if there is none yet and
+/// `create` is set.
+fn encryption_key(create: bool) -> Result<Vec<u8>, Box<dyn Error>> {
+    let entry = Entry::new(KEYRING_SERVICE, KEYRING_USER)?;
+
+    match entry.get_password() {
+        Ok(key) => Ok(BASE64_STANDARD.decode(key)?),
+        Err(keyring::Error::NoEntry) if create => {
+            let mut key = vec![0; KEY_LEN];
+            rand_bytes(&mut key)?;
+            entry.set_password(&BASE64_STANDARD.encode(&key))?;
+
+            Ok(key)
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Encrypts the data with a random nonce. The result contains the nonce, the ciphertext
+/// and the authentication tag.
+fn encrypt(key: &[u8], plaintext: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut nonce = [0; NONCE_LEN];
+    rand_bytes(&mut nonce)?;
+
+    let mut tag = [0; TAG_LEN];
+    let ciphertext = encrypt_aead(
+        Cipher::aes_256_gcm(),
+        key,
+        Some(&nonce),
+        &[],
+        plaintext,
+        &mut tag,
+    )?;
+
+    Ok([&nonce[..], &ciphertext, &tag].concat())
+}
+
+fn decrypt(key: &[u8], data: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+    if data.len() < NONCE_LEN + TAG_LEN {
+        return Err("The credential cache is corrupted".into());
+    }
+
+    let (nonce, rest) = data.split_at(NONCE_LEN);
+    let (ciphertext, tag) = rest.split_at(rest.len() - TAG_LEN);
+
+    decrypt_aead(
+        Cipher::aes_256_gcm(),
+        key,
+        Some(nonce),
+        &[],
+        ciphertext,
+        tag,
+    )
+    .map_err(|_| "The credential cache could not be decrypted".into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encrypted_data_can_only_be_read_with_the_key() {
+        let key = [7; KEY_LEN];
+        let data = encrypt(&key, b"session token").unwrap();
+
+        assert_eq!(decrypt(&key, &data).unwrap(), b"session token");
+        assert!(decrypt(&[8; KEY_LEN], &data).is_err());
+
+        let mut tampered = data.clone();
+        tampered[NONCE_LEN] ^= 1;
+        assert!(decrypt(&key, &tampered).is_err());
+    }
+}
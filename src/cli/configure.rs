@@ -0,0 +1,416 @@
+use config::{
+    default_filename, get_password, load_or_default, set_password, uses_keyring, Config, Idp,
+    PasswordSource, ENV_IDP_URL,
+};
+use saml2aws_auto::Secret;
+
+use crossterm::style::Stylize;
+use rpassword;
+use std::env;
+use std::fs;
+use std::io;
+use std::io::prelude::*;
+use std::panic;
+use std::path::Path;
+
+#[cfg(windows)]
+const LINE_ENDING: &'static str = "\r\n";
+#[cfg(not(windows))]
+const LINE_ENDING: &'static str = "\n";
+
+/// Path of the config file used by releases before the XDG directories were supported
+fn legacy_filename() -> String {
+    let mut path = dirs::home_dir().unwrap();
+    path.push(".saml2aws-auto.yml");
+
+    path.to_string_lossy().into_owned()
+}
+
+/// Returns the path of the config file to use if none was given. A config file at the
+/// legacy location is moved to the XDG config directory. If that fails, the legacy file
+/// keeps being used.
+pub fn locate_default_filename() -> String {
+    let path = default_filename();
+    let legacy = legacy_filename();
+
+    if Path::new(&path).exists() || !Path::new(&legacy).exists() {
+        return path;
+    }
+
+    move_legacy_file(&legacy, &path)
+}
+
+/// Moves the config file from the legacy location and returns the path of the file to
+/// use. The file is copied as is, it is upgraded when it is loaded.
+fn move_legacy_file(legacy: &str, path: &str) -> String {
+    let moved = Path::new(path)
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|_| fs::copy(legacy, path))
+        .and_then(|_| fs::remove_file(legacy));
+
+    match moved {
+        Ok(_) => {
+            eprintln!(
+                "Moved the config file from {} to {}",
+                legacy.yellow(),
+                path.yellow()
+            );
+            path.into()
+        }
+        Err(e) => {
+            error!("could not move the config file: {:?}", e);
+            let _ = fs::remove_file(path);
+            legacy.into()
+        }
+    }
+}
+
+pub fn ask_question(question: &str, default: Option<&str>) {
+    ask_question_to(&mut io::stdout(), question, default)
+}
+
+fn ask_question_to(out: &mut dyn Write, question: &str, default: Option<&str>) {
+    match default {
+        Some(default) => {
+            let _ = write!(
+                out,
+                "{} {}",
+                "?".green(),
+                format!("{} [{}]: ", question, default),
+            );
+        }
+        None => {
+            let _ = write!(out, "{} {}", "?".green(), format!("{}: ", question),);
+        }
+    }
+    out.flush().unwrap();
+}
+
+pub fn password_prompt(question: &str, default: Option<&str>) -> Option<Secret> {
+    let masked: Option<String> = match default {
+        Some(s) => {
+            if s.len() == 0 {
+                None
+            } else if s.len() < 4 {
+                let formatted = format!("{}***", s.get(0..1).unwrap()).to_owned();
+                Some(formatted)
+            } else {
+                let formatted = format!("{}{}", s.get(0..4).unwrap(), "*".repeat(s.len() - 4));
+                Some(formatted)
+            }
+        }
+        None => None,
+    };
+
+    ask_question(question, masked.as_ref().map(|s| s.as_str()));
+
+    let password = match rpassword::read_password() {
+        Ok(p) => Secret::from(p),
+        Err(_) => {
+            println!("Could not read password");
+            return default.map(|d| Secret::from(d.to_string()));
+        }
+    };
+
+    if password.expose() == LINE_ENDING || password.is_empty() {
+        return match default {
+            Some(default) => Some(Secret::from(default.to_string())),
+            None => password_prompt(question, default),
+        };
+    }
+
+    Some(Secret::from(password.expose().trim().to_string()))
+}
+
+pub fn prompt(question: &str, default: Option<&str>, allow_empty: bool) -> Option<String> {
+    prompt_to(&mut io::stdout(), question, default, allow_empty)
+}
+
+/// Prompts like `prompt`, writing the question to `out`, e.g. stderr if stdout carries data
+/// for other programs
+pub fn prompt_to(
+    out: &mut dyn Write,
+    question: &str,
+    default: Option<&str>,
+    allow_empty: bool,
+) -> Option<String> {
+    let mut buf = String::new();
+
+    ask_question_to(out, question, default);
+
+    if let Err(_) = io::stdin().read_line(&mut buf) {
+        let _ = writeln!(out, "Could not read line");
+        return default.map(|d| d.into());
+    }
+
+    if buf == LINE_ENDING {
+        return match default {
+            Some(default) => Some(default.into()),
+            None => {
+                if allow_empty {
+                    return None;
+                }
+
+                return prompt_to(out, question, default, allow_empty);
+            }
+        };
+    }
+
+    Some(buf.trim().into())
+}
+
+pub fn interactive_create(default: Config) {
+    println!("\nWelcome to saml2aws-auto. Let's configure a few things to get started.");
+    print_idp_hint();
+
+    let mut cfg = default;
+
+    let idp = interactive_idp(
+        None,
+        Idp {
+            url: cfg.idp_url.clone(),
+            username: cfg.username.clone(),
+            mfa_device: cfg.mfa_device.clone(),
+            password_source: cfg.password_source.clone(),
+            password: cfg.password.clone(),
+        },
+    );
+
+    cfg.idp_url = idp.url;
+    cfg.username = idp.username;
+    cfg.mfa_device = idp.mfa_device;
+    cfg.password_source = idp.password_source;
+    cfg.password = idp.password;
+
+    cfg.save().unwrap();
+    println!(
+        "\nAll set!\nIf you need to reconfigure your details, use {}",
+        "saml2aws-auto configure".yellow(),
+    );
+}
+
+/// Adds or reconfigures the named IdP
+pub fn interactive_create_idp(default: Config, name: &str) {
+    println!("\nConfiguring the IdP {}.", name.yellow());
+    print_idp_hint();
+
+    let mut cfg = default;
+
+    let existing = cfg.idps.get(name).cloned().unwrap_or(Idp {
+        url: "localhost".into(),
+        ..Default::default()
+    });
+    let idp = interactive_idp(Some(name), existing);
+    cfg.idps.insert(name.into(), idp);
+
+    cfg.save().unwrap();
+    println!(
+        "\nAll set!\nUse {} to log in with this IdP when adding groups.",
+        format!("--idp {}", name).yellow(),
+    );
+}
+
+fn print_idp_hint() {
+    println!("Currently, only Keycloak is supported as Identity Provider. When setting the");
+    println!(
+        "IDP URL, please note that you will have to pass {} of Keycloak.\n",
+        "the exact path to the saml client".yellow(),
+    );
+}
+
+/// Prompts for the settings of an IdP and stores its password in the keyring
+fn interactive_idp(name: Option<&str>, default: Idp) -> Idp {
+    let mut idp = default;
+
+    if let Some(url) = prompt("IDP URL", Some(&idp.url), false) {
+        idp.url = url;
+    }
+
+    loop {
+        let current = idp
+            .password_source
+            .clone()
+            .unwrap_or(PasswordSource::Keyring)
+            .to_string();
+
+        match prompt(
+            "Password source (keyring, env:VAR, command:CMD, file:PATH or stdin)",
+            Some(&current),
+            false,
+        )
+        .map(|s| s.parse::<PasswordSource>())
+        {
+            Some(Ok(PasswordSource::Keyring)) => idp.password_source = None,
+            Some(Ok(source)) => idp.password_source = Some(source),
+            Some(Err(e)) => {
+                println!("{}", e.red());
+                continue;
+            }
+            None => {}
+        }
+        break;
+    }
+
+    if let Some(username) = prompt("IDP Username", idp.username.as_deref(), false) {
+        // Passwords from other sources are read whenever they are needed
+        if uses_keyring(&idp.password_source) {
+            if let Some(password) = password_prompt(
+                "IDP Password",
+                match get_password(name, &username) {
+                    Ok(ref p) => {
+                        if p.is_empty() {
+                            None
+                        } else {
+                            Some(p.expose())
+                        }
+                    }
+                    Err(_) => Some(""),
+                },
+            ) {
+                trace!("interactive_idp.set_password");
+
+                match set_password(name, &username, &password) {
+                    Ok(_) => {}
+                    Err(e) => {
+                        error!("error saving password: {:?}", e);
+                        println!("Could not save password");
+                    }
+                };
+                idp.password = Some(password);
+            }
+        }
+        idp.username = Some(username);
+    }
+
+    idp.mfa_device = prompt(
+        "IDP MFA Device (leave empty if only using one device)",
+        None,
+        true,
+    );
+
+    idp
+}
+
+/// Loads the config for commands whose output is consumed by other programs. These never
+/// prompt, so errors are only printed to stderr.
+pub fn check_non_interactive(config_path: &str) -> bool {
+    match load_or_default(config_path) {
+        Ok(cfg) => {
+            print_upgrade_notice(&cfg);
+            true
+        }
+        Err(e) => {
+            eprintln!(
+                "Could not load the saml2aws-auto config file {}: {}",
+                config_path, e
+            );
+            false
+        }
+    }
+}
+
+fn print_upgrade_notice(cfg: &Config) {
+    if let Some(ref backup) = cfg.upgrade_backup {
+        eprintln!(
+            "Upgraded the config file to the current version. A backup of the previous version was written to {}",
+            backup.as_str().yellow()
+        );
+    }
+}
+
+pub fn check_or_interactive_create(config_path: &str, skip_password_prompt: bool) -> bool {
+    if !Path::new(config_path).exists() {
+        // The IdP can be configured through the environment, e.g. in containers
+        if env::var_os(ENV_IDP_URL).is_some() {
+            return true;
+        }
+
+        if let Some(dir) = Path::new(config_path).parent() {
+            if let Err(e) = fs::create_dir_all(dir) {
+                println!("{}: {}", "Could not create the config directory".red(), e);
+                return false;
+            }
+        }
+
+        interactive_create(Config::default(config_path));
+        return true;
+    }
+
+    let cfg = match load_or_default(config_path) {
+        Ok(c) => c,
+        Err(e) => {
+            println!(
+                "{}: {}",
+                "Could not load the saml2aws-auto config file".red(),
+                e
+            );
+            println!("\nPlease check that if you did any manual modifications that your YAML is still valid.");
+            println!("If the config was upgraded from an older release, a backup of the previous file is next to it (ending in .bak).");
+            return false;
+        }
+    };
+
+    print_upgrade_notice(&cfg);
+
+    if skip_password_prompt {
+        return true;
+    }
+
+    let idps = std::iter::once((None, cfg.idp(None).unwrap())).chain(
+        cfg.idps
+            .iter()
+            .map(|(name, idp)| (Some(name.as_str()), idp.clone())),
+    );
+
+    for (name, idp) in idps {
+        let username = match idp.username {
+            Some(ref u) if uses_keyring(&idp.password_source) => u,
+            _ => continue,
+        };
+
+        if let Err(_) = panic::catch_unwind(|| {
+            if let Err(_) = get_password(name, username) {
+                let question = match name {
+                    Some(name) => format!("IDP Password for {}", name),
+                    None => "IDP Password".into(),
+                };
+
+                if let Some(password) = password_prompt(&question, Some("")) {
+                    set_password(name, username, &password)
+                        .expect("Could not save password in credentials storage");
+                }
+            }
+        }) {
+            println!("\n{}: It seems like there is a problem with managing your credentials. Please use the '--password' flag in all commands for now.\nWe are working on a fix.",
+                         "WARNING".yellow());
+            return false;
+        };
+    }
+    return true;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn move_legacy_file_copies_the_config() {
+        let dir = env::temp_dir().join(format!("saml2aws-auto-legacy-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let legacy = dir.join(".saml2aws-auto.yml");
+        let path = dir.join("config/saml2aws-auto/config.yml");
+        fs::write(&legacy, include_str!("../config/fixtures/v1.13.yml")).unwrap();
+
+        let used = move_legacy_file(legacy.to_str().unwrap(), path.to_str().unwrap());
+
+        assert_eq!(used, path.to_str().unwrap());
+        assert!(!legacy.exists());
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            include_str!("../config/fixtures/v1.13.yml")
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,191 @@
+use std::process::Command;
+use std::str::FromStr;
+
+use chrono::prelude::*;
+use crossterm::style::Stylize;
+use serde_json;
+
+use config;
+use config::Storage;
+use saml2aws_auto::{load_credentials_file, CachedCredentials, CredentialCache, Secret};
+
+use crate::cli::ExecArgs;
+
+/// Runs the command with the credentials of the profile in its environment. Returns the
+/// exit code of the command.
+pub fn command(cfg: &config::Config, args: &ExecArgs) -> i32 {
+    let credentials = match find_credentials(cfg, &args.profile) {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("\nCould not run the command:\n\n\t{}\n", e.red());
+            return 1;
+        }
+    };
+
+    let mut command = Command::new(&args.command[0]);
+    command
+        .args(&args.command[1..])
+        .env_remove("AWS_PROFILE")
+        .env("AWS_ACCESS_KEY_ID", &credentials.access_key_id)
+        .env(
+            "AWS_SECRET_ACCESS_KEY",
+            credentials.secret_access_key.expose(),
+        )
+        .env("AWS_SESSION_TOKEN", credentials.session_token.expose())
+        .env("AWS_CREDENTIAL_EXPIRATION", &credentials.expiration);
+
+    if let Some(ref region) = credentials.region {
+        command
+            .env("AWS_REGION", region)
+            .env("AWS_DEFAULT_REGION", region);
+    }
+
+    match command.status() {
+        Ok(status) => status.code().unwrap_or(1),
+        Err(e) => {
+            eprintln!(
+                "\nCould not run {}:\n\n\t{}\n",
+                args.command[0].as_str().yellow(),
+                e.to_string().red()
+            );
+            1
+        }
+    }
+}
+
+/// Prints the credentials of the profile in the format expected by the
+/// `credential_process` setting of the AWS CLI and SDKs.
+pub fn credential_process(cfg: &config::Config, profile: &str) -> bool {
+    match find_credentials(cfg, profile) {
+        Ok(credentials) => {
+            let output = serde_json::json!({
+                "Version": 1,
+                "AccessKeyId": credentials.access_key_id,
+                "SecretAccessKey": credentials.secret_access_key,
+                "SessionToken": credentials.session_token,
+                "Expiration": credentials.expiration,
+            });
+
+            println!("{}", output);
+            true
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            false
+        }
+    }
+}
+
+/// Returns the valid credentials of the profile from the storage of its group. Profiles
+/// that are not part of a group are looked up in the encrypted cache and then in the AWS
+/// credentials file.
+fn find_credentials(cfg: &config::Config, profile: &str) -> Result<CachedCredentials, String> {
+    let storage = profile_storage(cfg, profile);
+    let mut found: Option<CachedCredentials> = None;
+
+    if storage != Some(Storage::Plaintext) {
+        let cache = CredentialCache::load(&cfg.directory()).map_err(|e| e.to_string())?;
+        found = cache.get(profile).cloned();
+    }
+
+    // Cached credentials can be left behind by a group that was switched to plaintext
+    if storage != Some(Storage::Encrypted) && !found.as_ref().is_some_and(is_valid) {
+        if let Some(credentials) = read_credentials_file(profile)? {
+            found = Some(credentials);
+        }
+    }
+
+    match found {
+        Some(ref credentials) if is_valid(credentials) => Ok(found.unwrap()),
+        Some(_) => Err(format!(
+            "The credentials of the profile {} have expired, please run saml2aws-auto refresh",
+            profile
+        )),
+        None => Err(format!("No credentials found for the profile {}", profile)),
+    }
+}
+
+/// Returns the storage of the group the profile belongs to
+fn profile_storage(cfg: &config::Config, profile: &str) -> Option<Storage> {
+    cfg.groups.iter().find_map(|(name, group)| {
+        group
+            .accounts
+            .iter()
+            .any(|a| group.profile_name(name, a) == profile)
+            .then_some(group.storage)
+    })
+}
+
+fn read_credentials_file(profile: &str) -> Result<Option<CachedCredentials>, String> {
+    let (file, _) = load_credentials_file().map_err(|e| e.to_string())?;
+    let section = match file.section(Some(profile)) {
+        Some(s) => s,
+        None => return Ok(None),
+    };
+    let get = |key: &str| {
+        section
+            .get(key)
+            .map(String::from)
+            .ok_or(format!("The profile {} has no {}", profile, key))
+    };
+
+    Ok(Some(CachedCredentials {
+        access_key_id: get("aws_access_key_id")?,
+        secret_access_key: Secret::from(get("aws_secret_access_key")?),
+        session_token: Secret::from(get("aws_session_token")?),
+        expiration: get("expiration")?,
+        region: section.get("region").map(String::from),
+    }))
+}
+
+fn is_valid(credentials: &CachedCredentials) -> bool {
+    DateTime::<FixedOffset>::from_str(&credentials.expiration)
+        .is_ok_and(|expiration| expiration > Local::now())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use config::{Account, Group};
+
+    #[test]
+    fn profiles_are_read_from_the_storage_of_their_group() {
+        let mut cfg = config::Config::default("test.yml");
+        cfg.groups.insert(
+            "payments".into(),
+            Group {
+                storage: Storage::Encrypted,
+                accounts: vec![Account {
+                    name: "payments-prod".into(),
+                    arn: "arn:aws:iam::123456789012:role/Administrator".into(),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
+        );
+
+        assert_eq!(
+            profile_storage(&cfg, "payments-prod"),
+            Some(Storage::Encrypted)
+        );
+        assert_eq!(profile_storage(&cfg, "search-prod"), None);
+    }
+
+    #[test]
+    fn expired_credentials_are_not_valid() {
+        let credentials = |expiration: DateTime<Local>| CachedCredentials {
+            access_key_id: "AKIA".into(),
+            secret_access_key: Secret::from("secret".to_string()),
+            session_token: Secret::from("token".to_string()),
+            expiration: expiration.to_rfc3339(),
+            region: None,
+        };
+
+        assert!(is_valid(&credentials(
+            Local::now() + chrono::Duration::hours(1)
+        )));
+        assert!(!is_valid(&credentials(
+            Local::now() - chrono::Duration::hours(1)
+        )));
+    }
+}
@@ -0,0 +1,1079 @@
+use cli::configure::prompt;
+use cli::picker::pick_roles;
+use config;
+use config::{Account, AccountSelector, Group, Storage};
+use saml2aws_auto::{fetch_roles, load_credentials_file, CredentialCache, Secret};
+
+use chrono::prelude::*;
+use crossterm::style::Stylize;
+use serde_yaml;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+use std::io;
+use std::io::prelude::*;
+use std::io::IsTerminal;
+
+use crate::cli::{AddGroupArgs, EditAccountArgs, EditGroupArgs, GroupCommands};
+
+pub fn command(cfg: &mut config::Config, command: &GroupCommands) {
+    match command {
+        GroupCommands::List => list(cfg),
+        GroupCommands::Delete { group } => delete(cfg, group),
+        GroupCommands::Add(args) => add_group(cfg, args),
+        GroupCommands::Show { group } => show(cfg, group),
+        GroupCommands::Edit(args) => edit(cfg, args),
+        GroupCommands::EditAccount(args) => edit_account(cfg, args),
+        GroupCommands::Export { groups } => export(cfg, groups),
+        GroupCommands::Import { file, append } => import(cfg, file, *append),
+        GroupCommands::Compose { group, groups } => compose(cfg, group, groups),
+        GroupCommands::Rename { group, new_name } => rename(cfg, group, new_name),
+    }
+}
+
+fn add_group(cfg: &mut config::Config, args: &AddGroupArgs) {
+    let idp = match cfg.idp(args.idp.as_deref()) {
+        Some(idp) => idp,
+        None => {
+            println!(
+                "\nCould not add group {}:\n\n\t{}\n",
+                args.name.clone().yellow(),
+                "The specified IdP does not exist".red(),
+            );
+            return;
+        }
+    };
+
+    let username = match args.username.as_deref().or(idp.username.as_deref()) {
+        Some(username) => username,
+        None => {
+            println!(
+                "\nCould not add group {}:\n\n\t{}\n",
+                args.name.clone().yellow(),
+                "No username configured, pass --username or run saml2aws-auto configure".red(),
+            );
+            return;
+        }
+    };
+
+    let password = match args
+        .password
+        .clone()
+        .map_or_else(|| idp.resolve_password(), Ok)
+    {
+        Ok(p) => p,
+        Err(e) => {
+            println!(
+                "\nCould not add group {}:\n\n\t{}\n",
+                args.name.clone().yellow(),
+                e.red(),
+            );
+            return;
+        }
+    };
+
+    let selector = selector_from_args(args);
+
+    if let Err(e) = selector.validate() {
+        println!(
+            "\nCould not add group {}:\n\n\t{}\n",
+            args.name.clone().yellow(),
+            e.to_string().red(),
+        );
+        return;
+    }
+
+    // Without any account filters, the accounts are picked interactively
+    let interactive = !selector.has_account_filters();
+
+    if interactive && !io::stdin().is_terminal() {
+        println!(
+            "\nCould not add group {}:\n\n\t{}\n",
+            args.name.clone().yellow(),
+            "Must specify at least one of --prefix, --accounts, --account-id or --match".red(),
+        );
+        return;
+    }
+
+    if !interactive && !selector.has_role_filters() {
+        println!(
+            "\nCould not add group {}:\n\n\t{}\n",
+            args.name.clone().yellow(),
+            "Must specify --role or --role-match when selecting accounts with flags".red(),
+        );
+        return;
+    }
+
+    let mfa = args
+        .mfa
+        .clone()
+        .or_else(|| prompt("MFA Token", Some("000000"), false).map(Secret::from))
+        .expect("No MFA Token provided");
+
+    let mut accounts: Vec<Account> = vec![];
+
+    print!("Listing allowed roles for your account\t");
+    io::stdout().flush().unwrap();
+    trace!("command.fetch_roles");
+
+    let aws_list = match fetch_roles(&idp, username, &password, &mfa) {
+        Ok(l) => l,
+        Err(e) => {
+            trace!("command.fetch_roles.err");
+            error!("{:?}", e);
+            println!("{}", "FAIL".red());
+            println!("\nCould not add group:\n\n\t{}\n", e.to_string().red());
+            return;
+        }
+    };
+
+    if aws_list.len() == 1 {
+        // This is a special case because the user will never see a role list form
+        // on the web console. We will now add a single account with the account id
+        // and ask the user for a name.
+
+        println!("\t{}", "WARNING".yellow());
+        println!("\nYou seem to only have access to a single AWS Account. The name could not be found automatically, so please enter an account name manually.");
+
+        let account_name = prompt("Account name", None, false).unwrap();
+
+        accounts = vec![Account {
+            name: account_name,
+            arn: aws_list[0].arn.clone(),
+            ..Default::default()
+        }];
+    } else if !interactive {
+        // The regular expressions have already been validated
+        accounts = selector.select(&aws_list).unwrap();
+    } else {
+        println!("\t{}", "SUCCESS".green());
+
+        let title = format!("Select the accounts for group {}", args.name);
+        match pick_roles(&title, &aws_list) {
+            Ok(Some(roles)) => {
+                accounts = roles
+                    .into_iter()
+                    .map(|r| Account {
+                        name: r.account_name,
+                        arn: r.arn,
+                        ..Default::default()
+                    })
+                    .collect();
+            }
+            Ok(None) => {
+                println!(
+                    "\nCancelled, group {} was not changed.\n",
+                    args.name.as_str().yellow()
+                );
+                return;
+            }
+            Err(e) => {
+                error!("{:?}", e);
+                println!("\nCould not add group:\n\n\t{}\n", e.to_string().red());
+                return;
+            }
+        };
+
+        if accounts.is_empty() {
+            println!(
+                "\nNo accounts were selected, group {} was not changed.\n",
+                args.name.as_str().yellow()
+            );
+            return;
+        }
+    }
+
+    if accounts.is_empty() {
+        println!("\t{}", "WARNING".yellow());
+        println!("\nNo accounts were found with the given parameters. Possible errors:");
+        println!("\t- Wrong prefix/accounts/patterns used");
+        println!("\t- Wrong role used");
+
+        trace!("aws_list");
+        for account in &aws_list {
+            trace!("aws_list name={} arn={}", account.account_name, account.arn);
+        }
+    } else {
+        if !interactive {
+            println!("\t{}", "SUCCESS".green());
+        }
+        // Groups selected with flags are kept in sync with the IdP on refresh
+        let selectors = match interactive || aws_list.len() == 1 {
+            true => vec![],
+            false => vec![selector],
+        };
+
+        add(
+            cfg,
+            &args.name,
+            Group {
+                session_duration: args.session_duration,
+                sts_endpoint: args.sts_endpoint.clone(),
+                min_remaining: args.min_remaining,
+                region: None,
+                profile_template: None,
+                idp: args.idp.clone(),
+                storage: args.storage,
+                selectors,
+                includes: vec![],
+                accounts,
+            },
+            args.append,
+        )
+    }
+}
+
+fn list(cfg: &config::Config) {
+    for (name, group) in &cfg.groups {
+        print_group(name, group);
+    }
+}
+
+fn show(cfg: &config::Config, name: &str) {
+    match cfg.groups.get(name) {
+        Some(group) => print_group(name, group),
+        None => println!(
+            "\nCould not show the group {}:\n\n\t{}\n",
+            name.yellow(),
+            "The specified group does not exist".red()
+        ),
+    }
+}
+
+fn print_group(name: &str, group: &Group) {
+    println!("\n{}:", name.yellow());
+
+    if let Some(duration) = group.session_duration {
+        println!(
+            "\t{}: {}",
+            "Session Duration",
+            format!("{} seconds", duration).blue()
+        );
+    } else {
+        println!("\t{}: {}", "Session Duration", "implicit".blue(),);
+    }
+
+    if let Some(endpoint) = &group.sts_endpoint {
+        println!("\t{}: {}", "STS Endpoint", endpoint.as_str().blue(),);
+    } else {
+        println!("\t{}: {}", "STS Endpoint", "default".blue());
+    }
+
+    if let Some(min_remaining) = group.min_remaining {
+        println!(
+            "\tMinimum Remaining: {}",
+            format!("{} seconds", min_remaining).blue()
+        );
+    }
+
+    if let Some(region) = &group.region {
+        println!("\tRegion: {}", region.as_str().blue());
+    }
+
+    if let Some(template) = &group.profile_template {
+        println!("\tProfile Template: {}", template.as_str().blue());
+    }
+
+    if let Some(idp) = &group.idp {
+        println!("\tIdP: {}", idp.as_str().blue());
+    }
+
+    if group.storage == Storage::Encrypted {
+        println!("\tStorage: {}", "encrypted".blue());
+    }
+
+    if !group.includes.is_empty() {
+        println!("\tIncludes: {}", group.includes.join(", ").blue());
+    }
+
+    // Composite groups without accounts of their own
+    if group.accounts.is_empty() && !group.includes.is_empty() {
+        println!();
+        return;
+    }
+
+    if !group.selectors.is_empty() {
+        println!("\n\tSelectors (synchronised with the IdP on refresh)");
+        for selector in &group.selectors {
+            println!("\t{}", selector);
+        }
+    }
+
+    println!("\n\t{}", "Sessions");
+    for account in &group.accounts {
+        if account.disabled {
+            println!("\t{}: {}", &account.name, "disabled".dark_grey());
+            continue;
+        }
+
+        match account.valid_until {
+            Some(expiration) => {
+                let now = Local::now();
+
+                let expiration = expiration.signed_duration_since(now);
+                if expiration.num_minutes() < 0 {
+                    println!("\t{}: {}", &account.name, "no valid session".red(),);
+                } else {
+                    println!(
+                        "\t{}: {}",
+                        &account.name,
+                        format!("{} minutes left", expiration.num_minutes()).green()
+                    );
+                }
+            }
+            None => {
+                println!("\t{}: {}", &account.name, "no valid session".red(),);
+            }
+        };
+    }
+
+    println!("\n\tARNs");
+    for account in &group.accounts {
+        println!("\t{}: {}", &account.name, account.arn,);
+    }
+
+    let overrides: Vec<(&String, Vec<String>)> = group
+        .accounts
+        .iter()
+        .map(|a| (&a.name, account_overrides(a)))
+        .filter(|(_, o)| !o.is_empty())
+        .collect();
+
+    if !overrides.is_empty() {
+        println!("\n\tAccount Overrides");
+        for (name, overrides) in overrides {
+            println!("\t{}: {}", name, overrides.join(", ").blue());
+        }
+    }
+    println!("");
+}
+
+fn account_overrides(account: &Account) -> Vec<String> {
+    let mut overrides: Vec<String> = vec![];
+
+    if let Some(duration) = account.session_duration {
+        overrides.push(format!("session duration {} seconds", duration));
+    }
+    if let Some(endpoint) = &account.sts_endpoint {
+        overrides.push(format!("sts endpoint {}", endpoint));
+    }
+    if let Some(region) = &account.region {
+        overrides.push(format!("region {}", region));
+    }
+    if let Some(profile) = &account.profile {
+        overrides.push(format!("profile {}", profile));
+    }
+
+    overrides
+}
+
+fn delete(cfg: &mut config::Config, name: &str) {
+    if !cfg.groups.contains_key(name) {
+        println!(
+            "\nCould not delete the group {}:\n\n\t{}\n",
+            name.yellow(),
+            "The specified group does not exist".red()
+        );
+        return;
+    }
+    cfg.groups.remove(name).unwrap();
+
+    for (composite, group) in cfg.groups.iter_mut() {
+        if group.includes.iter().any(|n| n == name) {
+            group.includes.retain(|n| n != name);
+            println!(
+                "Removed group {} from composite group {}",
+                name.yellow(),
+                composite.as_str().yellow()
+            );
+        }
+    }
+
+    cfg.save().unwrap();
+    println!("\nSuccessfully deleted group {}.\n", name.yellow(),);
+}
+
+fn edit(cfg: &mut config::Config, args: &EditGroupArgs) {
+    let name = args.group.as_str();
+
+    if let Some(ref idp) = args.idp {
+        if !cfg.idps.contains_key(idp) {
+            println!(
+                "\nCould not edit the group {}:\n\n\t{}\n",
+                name.yellow(),
+                format!("The IdP {} does not exist", idp).red()
+            );
+            return;
+        }
+    }
+
+    let group = match cfg.groups.get_mut(name) {
+        Some(g) => g,
+        None => {
+            println!(
+                "\nCould not edit the group {}:\n\n\t{}\n",
+                name.yellow(),
+                "The specified group does not exist".red()
+            );
+            return;
+        }
+    };
+
+    for account in &args.add_accounts {
+        if group.accounts.iter().any(|a| a.name == account.name) {
+            println!(
+                "\nCould not edit the group {}:\n\n\t{}\n",
+                name.yellow(),
+                format!("The account {} is already part of the group", account.name).red()
+            );
+            return;
+        }
+    }
+
+    for account_name in &args.remove_accounts {
+        if !group.accounts.iter().any(|a| &a.name == account_name) {
+            println!(
+                "\nCould not edit the group {}:\n\n\t{}\n",
+                name.yellow(),
+                format!("The account {} is not part of the group", account_name).red()
+            );
+            return;
+        }
+    }
+
+    let profiles = group.profiles(name);
+
+    update(
+        &mut group.session_duration,
+        &args.session_duration,
+        args.unset_session_duration,
+    );
+    update(
+        &mut group.sts_endpoint,
+        &args.sts_endpoint,
+        args.unset_sts_endpoint,
+    );
+    update(&mut group.region, &args.region, args.unset_region);
+    update(
+        &mut group.profile_template,
+        &args.profile_template,
+        args.unset_profile_template,
+    );
+    update(
+        &mut group.min_remaining,
+        &args.min_remaining,
+        args.unset_min_remaining,
+    );
+    update(&mut group.idp, &args.idp, args.unset_idp);
+
+    // Credentials are only written to the new storage on refresh, so the sessions are
+    // expired and the credentials are removed from the previous storage
+    let moved_storage = match args.storage {
+        Some(storage) if storage != group.storage => {
+            let previous = group.storage;
+            group.storage = storage;
+            for account in &mut group.accounts {
+                account.valid_until = None;
+            }
+            Some(previous)
+        }
+        _ => None,
+    };
+
+    // Profiles written to a new name or region are written again on the next refresh
+    group.expire_moved_profiles(name, &profiles);
+
+    if !args.add_accounts.is_empty() || !args.remove_accounts.is_empty() {
+        group
+            .accounts
+            .retain(|a| !args.remove_accounts.contains(&a.name));
+        group.accounts.extend(args.add_accounts.iter().cloned());
+
+        // Accounts changed by hand would be overwritten by the next synchronisation
+        if !group.selectors.is_empty() {
+            println!(
+                "Group {} will no longer be synchronised with the IdP on refresh",
+                name
+            );
+            group.selectors.clear();
+        }
+    }
+
+    if let Some(previous) = moved_storage {
+        let profiles: Vec<String> = profiles.into_values().map(|(p, _)| p).collect();
+
+        if let Err(e) = remove_credentials(cfg, previous, &profiles) {
+            println!(
+                "{}: The credentials of the group are still stored in {}: {}",
+                "WARNING".yellow(),
+                storage_name(previous),
+                e
+            );
+        }
+    }
+
+    cfg.save().unwrap();
+    println!("\nGroup configuration updated");
+
+    show(cfg, name);
+}
+
+fn storage_name(storage: Storage) -> &'static str {
+    match storage {
+        Storage::Plaintext => "~/.aws/credentials",
+        Storage::Encrypted => "the encrypted credential cache",
+    }
+}
+
+/// Removes the credentials of the profiles from the storage
+fn remove_credentials(
+    cfg: &config::Config,
+    storage: Storage,
+    profiles: &[String],
+) -> Result<(), Box<dyn Error>> {
+    match storage {
+        Storage::Plaintext => {
+            let (mut file, path) = load_credentials_file()?;
+            for profile in profiles {
+                file.delete(Some(profile.as_str()));
+            }
+            file.write_to_file(path)?;
+        }
+        Storage::Encrypted => {
+            let mut cache = CredentialCache::load(&cfg.directory())?;
+            if profiles.iter().filter(|p| cache.remove(p)).count() > 0 {
+                cache.save()?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn edit_account(cfg: &mut config::Config, args: &EditAccountArgs) {
+    let name = args.group.as_str();
+
+    let group = match cfg.groups.get_mut(name) {
+        Some(g) => g,
+        None => {
+            println!(
+                "\nCould not edit the group {}:\n\n\t{}\n",
+                name.yellow(),
+                "The specified group does not exist".red()
+            );
+            return;
+        }
+    };
+
+    let profiles = group.profiles(name);
+
+    let account = match group.accounts.iter_mut().find(|a| a.name == args.account) {
+        Some(a) => a,
+        None => {
+            println!(
+                "\nCould not edit the group {}:\n\n\t{}\n",
+                name.yellow(),
+                format!("The account {} is not part of the group", args.account).red()
+            );
+            return;
+        }
+    };
+
+    update(
+        &mut account.session_duration,
+        &args.session_duration,
+        args.unset_session_duration,
+    );
+    update(
+        &mut account.sts_endpoint,
+        &args.sts_endpoint,
+        args.unset_sts_endpoint,
+    );
+    update(&mut account.region, &args.region, args.unset_region);
+    update(&mut account.profile, &args.profile, args.unset_profile);
+
+    if args.disable {
+        account.disabled = true;
+    } else if args.enable {
+        account.disabled = false;
+    }
+
+    // A profile written to a new name or region is written again on the next refresh
+    group.expire_moved_profiles(name, &profiles);
+
+    cfg.save().unwrap();
+    println!("\nGroup configuration updated");
+
+    show(cfg, name);
+}
+
+/// Sets `field` to `value` if it is given, or clears it if `unset` is true
+fn update<T: Clone>(field: &mut Option<T>, value: &Option<T>, unset: bool) {
+    if value.is_some() {
+        *field = value.clone();
+    } else if unset {
+        *field = None;
+    }
+}
+
+/// GroupExport is the file format of group exports. Groups are sorted by name to keep
+/// exports stable.
+#[derive(Debug, Serialize, Deserialize)]
+struct GroupExport {
+    groups: BTreeMap<String, Group>,
+}
+
+fn export(cfg: &config::Config, names: &[String]) {
+    for name in names {
+        if !cfg.groups.contains_key(name) {
+            eprintln!(
+                "\nCould not export the group {}:\n\n\t{}\n",
+                name.as_str().yellow(),
+                "The specified group does not exist".red()
+            );
+            return;
+        }
+    }
+
+    print!(
+        "{}",
+        serde_yaml::to_string(&export_groups(cfg, names)).unwrap()
+    );
+}
+
+/// Returns the groups with the given names, or all groups if no name is given, without
+/// their sessions
+fn export_groups(cfg: &config::Config, names: &[String]) -> GroupExport {
+    let groups: BTreeMap<String, Group> = cfg
+        .groups
+        .iter()
+        .filter(|(name, _)| names.is_empty() || names.contains(name))
+        .map(|(name, group)| {
+            let mut group = group.clone();
+            for account in &mut group.accounts {
+                account.valid_until = None;
+            }
+            (name.clone(), group)
+        })
+        .collect();
+
+    GroupExport { groups }
+}
+
+fn import(cfg: &mut config::Config, path: &str, append_only: bool) {
+    let export: GroupExport = match fs::read_to_string(path)
+        .map_err(|e| e.to_string())
+        .and_then(|buf| serde_yaml::from_str(&buf).map_err(|e| e.to_string()))
+    {
+        Ok(e) => e,
+        Err(e) => {
+            println!(
+                "\nCould not import groups from {}:\n\n\t{}\n",
+                path.yellow(),
+                e.red()
+            );
+            return;
+        }
+    };
+
+    if let Err(e) = import_groups(cfg, export, append_only) {
+        println!(
+            "\nCould not import groups from {}:\n\n\t{}\n",
+            path.yellow(),
+            e.red()
+        );
+        return;
+    }
+
+    cfg.save().unwrap();
+    println!("\nGroup configuration updated");
+}
+
+/// Adds the exported groups to the config. Existing groups are replaced, or extended
+/// if `append_only` is set. If any of the resulting groups is invalid, the config is left
+/// unchanged.
+fn import_groups(
+    cfg: &mut config::Config,
+    export: GroupExport,
+    append_only: bool,
+) -> Result<(), String> {
+    let previous = cfg.groups.clone();
+    let names: Vec<String> = export.groups.keys().cloned().collect();
+
+    for (name, mut imported) in export.groups {
+        for account in &mut imported.accounts {
+            account.valid_until = None;
+        }
+
+        let group = match cfg.groups.get_mut(&name) {
+            Some(g) => g,
+            None => {
+                println!("Adding group {}", name.as_str().yellow());
+                cfg.groups.insert(name, imported);
+                continue;
+            }
+        };
+
+        if append_only {
+            println!(
+                "Group {} exists, appending new accounts",
+                name.as_str().yellow()
+            );
+
+            append_selectors(&name, group, &imported.selectors);
+            for account in imported.accounts {
+                if !group.accounts.iter().any(|a| a.name == account.name) {
+                    group.accounts.push(account);
+                }
+            }
+            for include in imported.includes {
+                if !group.includes.contains(&include) {
+                    group.includes.push(include);
+                }
+            }
+
+            group.session_duration = imported.session_duration.or(group.session_duration);
+            group.sts_endpoint = imported.sts_endpoint.or(group.sts_endpoint.take());
+            group.min_remaining = imported.min_remaining.or(group.min_remaining);
+            group.region = imported.region.or(group.region.take());
+            group.profile_template = imported.profile_template.or(group.profile_template.take());
+            group.idp = imported.idp.or(group.idp.take());
+            if imported.storage == Storage::Encrypted {
+                group.storage = Storage::Encrypted;
+            }
+        } else {
+            println!(
+                "Group {} exists, replacing accounts",
+                name.as_str().yellow()
+            );
+
+            // Keep the sessions of accounts that did not change
+            for account in &mut imported.accounts {
+                if let Some(existing) = group
+                    .accounts
+                    .iter()
+                    .find(|a| a.name == account.name && a.arn == account.arn)
+                {
+                    account.valid_until = existing.valid_until;
+                }
+            }
+
+            *group = imported;
+        }
+    }
+
+    if let Err(e) = names.iter().try_for_each(|name| cfg.check_group(name)) {
+        cfg.groups = previous;
+        return Err(e);
+    }
+
+    Ok(())
+}
+
+/// Adds the selectors to a group before new accounts are appended to it. Selectors can
+/// only be combined if all accounts of the group were selected by one. Otherwise, the
+/// group stays static.
+fn append_selectors(name: &str, group: &mut Group, selectors: &[AccountSelector]) {
+    if selectors.is_empty() {
+        if !group.selectors.is_empty() {
+            println!(
+                "Group {} will no longer be synchronised with the IdP on refresh",
+                name
+            );
+            group.selectors.clear();
+        }
+    } else if !group.selectors.is_empty() || group.accounts.is_empty() {
+        for selector in selectors {
+            if !group.selectors.contains(selector) {
+                group.selectors.push(selector.clone());
+            }
+        }
+    }
+}
+
+fn compose(cfg: &mut config::Config, name: &str, includes: &[String]) {
+    let error = match cfg.resolve_groups(includes) {
+        Ok(ref resolved) if resolved.iter().any(|n| n == name) => {
+            Some("A group cannot include itself".to_string())
+        }
+        Ok(_) => None,
+        Err(e) => Some(e),
+    };
+
+    if let Some(e) = error {
+        println!(
+            "\nCould not compose the group {}:\n\n\t{}\n",
+            name.yellow(),
+            e.red()
+        );
+        return;
+    }
+
+    match cfg.groups.get_mut(name) {
+        Some(group) => {
+            println!("Group {} exists, replacing included groups", name.yellow());
+            group.includes = includes.to_vec();
+        }
+        None => {
+            cfg.groups.insert(
+                name.into(),
+                Group {
+                    session_duration: None,
+                    sts_endpoint: None,
+                    min_remaining: None,
+                    region: None,
+                    profile_template: None,
+                    idp: None,
+                    storage: Storage::Plaintext,
+                    selectors: vec![],
+                    includes: includes.to_vec(),
+                    accounts: vec![],
+                },
+            );
+        }
+    }
+
+    cfg.save().unwrap();
+    println!("\nGroup configuration updated");
+}
+
+fn rename(cfg: &mut config::Config, name: &str, new_name: &str) {
+    if !cfg.groups.contains_key(name) {
+        println!(
+            "\nCould not rename the group {}:\n\n\t{}\n",
+            name.yellow(),
+            "The specified group does not exist".red()
+        );
+        return;
+    }
+
+    if cfg.groups.contains_key(new_name) {
+        println!(
+            "\nCould not rename the group {}:\n\n\t{}\n",
+            name.yellow(),
+            format!("A group named {} already exists", new_name).red()
+        );
+        return;
+    }
+
+    let mut group = cfg.groups.remove(name).unwrap();
+    let profiles = group.profiles(name);
+    group.expire_moved_profiles(new_name, &profiles);
+    cfg.groups.insert(new_name.into(), group);
+
+    for group in cfg.groups.values_mut() {
+        for include in &mut group.includes {
+            if include == name {
+                *include = new_name.into();
+            }
+        }
+    }
+
+    cfg.save().unwrap();
+    println!(
+        "\nSuccessfully renamed group {} to {}.\n",
+        name.yellow(),
+        new_name.yellow()
+    );
+}
+
+fn add(cfg: &mut config::Config, name: &str, new_group: Group, append_only: bool) {
+    let mut exists = false;
+
+    if let Some((name, group)) = cfg.groups.iter_mut().find(|&(a, _)| a == name) {
+        if append_only {
+            println!("Group {} exists, appending new accounts", name);
+
+            append_selectors(name, group, &new_group.selectors);
+
+            let existing_names: Vec<String> = (&group.accounts)
+                .into_iter()
+                .map(|ref a| a.name.clone())
+                .collect();
+
+            group.accounts.extend(
+                (&new_group.accounts)
+                    .into_iter()
+                    .filter(|a| !existing_names.contains(&a.name))
+                    .map(|a| a.clone())
+                    .collect::<Vec<Account>>(),
+            );
+        } else {
+            group.accounts = new_group.accounts.clone();
+            group.selectors = new_group.selectors.clone();
+            println!("Group {} exists, replacing accounts", name);
+        }
+        group.session_duration = new_group.session_duration;
+        group.min_remaining = new_group.min_remaining;
+        group.idp = new_group.idp.clone();
+        group.storage = new_group.storage;
+
+        // Extra logic: if the sts endpoint was set explicitly, assign it to the group
+        // if the parameter is not present, but there was a previous configuration,
+        // reset the sts endpoint to None
+        if new_group.sts_endpoint.is_some() {
+            group.sts_endpoint = new_group.sts_endpoint.clone();
+        } else if group.sts_endpoint.is_some() && new_group.sts_endpoint.is_none() {
+            group.sts_endpoint = None;
+        }
+        exists = true;
+    };
+
+    if !exists {
+        println!("Adding group {}", name);
+
+        cfg.groups.insert(name.into(), new_group);
+    }
+
+    println!("\n{}:", name.yellow());
+
+    for account in &cfg.groups.get(name).unwrap().accounts {
+        println!("\t{}: {}", account.name, account.arn,);
+    }
+
+    cfg.save().unwrap();
+    println!("\nGroup configuration updated");
+}
+
+fn selector_from_args(args: &AddGroupArgs) -> AccountSelector {
+    AccountSelector {
+        prefix: args.prefix.clone(),
+        accounts: args.accounts.clone().unwrap_or_default(),
+        account_ids: args.account_ids.clone().unwrap_or_default(),
+        matches: args.matches.clone(),
+        exclude: args.exclude.clone(),
+        roles: args.roles.clone(),
+        role_match: args.role_match.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn account(name: &str) -> Account {
+        Account {
+            name: name.into(),
+            arn: format!("arn:aws:iam::123456789012:role/{}", name),
+            valid_until: Some(Local::now().fixed_offset()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn export_and_import_round_trip() {
+        let mut cfg = config::Config::default("test.yml");
+        cfg.groups.insert(
+            "payments".into(),
+            Group {
+                region: Some("eu-central-1".into()),
+                storage: Storage::Encrypted,
+                selectors: vec![AccountSelector {
+                    prefix: Some("payments-".into()),
+                    roles: vec!["Administrator".into()],
+                    ..Default::default()
+                }],
+                accounts: vec![account("payments-prod")],
+                ..Default::default()
+            },
+        );
+
+        let buf = serde_yaml::to_string(&export_groups(&cfg, &[])).unwrap();
+
+        let mut imported = config::Config::default("test.yml");
+        import_groups(&mut imported, serde_yaml::from_str(&buf).unwrap(), false).unwrap();
+
+        let mut expected = cfg.groups["payments"].clone();
+        expected.accounts[0].valid_until = None;
+        assert_eq!(imported.groups["payments"], expected);
+    }
+
+    #[test]
+    fn import_append_keeps_static_groups_static() {
+        let mut cfg = config::Config::default("test.yml");
+        cfg.groups.insert(
+            "payments".into(),
+            Group {
+                accounts: vec![account("payments-prod")],
+                ..Default::default()
+            },
+        );
+
+        let mut groups = BTreeMap::new();
+        groups.insert(
+            "payments".to_string(),
+            Group {
+                selectors: vec![AccountSelector {
+                    prefix: Some("payments-".into()),
+                    ..Default::default()
+                }],
+                accounts: vec![account("payments-dev")],
+                ..Default::default()
+            },
+        );
+        import_groups(&mut cfg, GroupExport { groups }, true).unwrap();
+
+        let group = &cfg.groups["payments"];
+        assert!(group.selectors.is_empty());
+        assert_eq!(group.accounts.len(), 2);
+    }
+
+    #[test]
+    fn import_rejects_invalid_groups() {
+        let mut cfg = config::Config::default("test.yml");
+        cfg.groups.insert(
+            "payments".into(),
+            Group {
+                accounts: vec![account("payments-prod")],
+                ..Default::default()
+            },
+        );
+
+        let import = |groups: Vec<(&str, Group)>| GroupExport {
+            groups: groups
+                .into_iter()
+                .map(|(n, g)| (n.to_string(), g))
+                .collect(),
+        };
+        let including = |names: &[&str]| Group {
+            includes: names.iter().map(|n| n.to_string()).collect(),
+            ..Default::default()
+        };
+
+        let invalid = vec![
+            import(vec![("all", including(&["search"]))]),
+            import(vec![("a", including(&["b"])), ("b", including(&["a"]))]),
+            import(vec![(
+                "search",
+                Group {
+                    idp: Some("partner".into()),
+                    ..Default::default()
+                },
+            )]),
+            import(vec![(
+                "payments",
+                Group {
+                    selectors: vec![AccountSelector {
+                        matches: Some("(".into()),
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                },
+            )]),
+        ];
+
+        for export in invalid {
+            assert!(import_groups(&mut cfg, export, false).is_err());
+            assert_eq!(cfg.groups.len(), 1);
+            assert_eq!(cfg.groups["payments"].accounts.len(), 1);
+        }
+
+        import_groups(
+            &mut cfg,
+            import(vec![("all", including(&["payments"]))]),
+            false,
+        )
+        .unwrap();
+        assert_eq!(cfg.groups.len(), 2);
+    }
+}
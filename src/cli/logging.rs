@@ -0,0 +1,139 @@
+use std::fs;
+use std::fs::OpenOptions;
+use std::io;
+use std::path::Path;
+
+use chrono::prelude::*;
+use fern;
+use log::{LevelFilter, Record};
+use serde_json;
+
+use saml2aws_auto::{current_spans, redact};
+
+/// Log files larger than this are rotated when saml2aws-auto starts
+const MAX_LOG_SIZE: u64 = 5 * 1024 * 1024;
+/// Number of rotated log files that are kept, e.g. `saml2aws-auto.log.1` to `.5`
+const ROTATED_LOGS: u32 = 5;
+
+/// Sets up logging to stdout if `verbose` is set, and to the log file if one is given.
+/// Secrets are redacted from both.
+pub fn init(verbose: bool, log_file: Option<&str>) -> Result<(), io::Error> {
+    if !verbose && log_file.is_none() {
+        return Ok(());
+    }
+
+    let mut dispatch = fern::Dispatch::new()
+        .level(LevelFilter::Off)
+        .level_for("saml2aws_auto", LevelFilter::Trace);
+
+    if verbose {
+        dispatch = dispatch.chain(
+            fern::Dispatch::new()
+                .format(|out, message, record| {
+                    out.finish(format_args!(
+                        "[{}][{}] {}",
+                        record.level(),
+                        record.target(),
+                        redact(&message.to_string())
+                    ))
+                })
+                .chain(io::stdout()),
+        );
+    }
+
+    if let Some(path) = log_file {
+        dispatch = dispatch.chain(
+            fern::Dispatch::new()
+                .format(|out, message, record| {
+                    out.finish(format_args!("{}", json_line(&message.to_string(), record)))
+                })
+                .chain(open_log_file(path)?),
+        );
+    }
+
+    dispatch.apply().map_err(io::Error::other)
+}
+
+fn json_line(message: &str, record: &Record) -> serde_json::Value {
+    serde_json::json!({
+        "timestamp": Local::now().to_rfc3339_opts(SecondsFormat::Millis, false),
+        "level": record.level().to_string(),
+        "target": record.target(),
+        "spans": current_spans(),
+        "message": redact(message),
+    })
+}
+
+/// Opens the log file for appending, after rotating it if it grew too large
+fn open_log_file(path: &str) -> Result<fs::File, io::Error> {
+    if let Some(dir) = Path::new(path).parent() {
+        if !dir.as_os_str().is_empty() {
+            fs::create_dir_all(dir)?;
+        }
+    }
+
+    if fs::metadata(path).is_ok_and(|m| m.len() > MAX_LOG_SIZE) {
+        rotate(path)?;
+    }
+
+    let mut options = OpenOptions::new();
+    options.create(true).append(true);
+
+    // The log may contain account names and URLs, so only the user can read it
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+
+    options.open(path)
+}
+
+/// Renames `path` to `path.1`, `path.1` to `path.2` and so on. The oldest file is removed.
+fn rotate(path: &str) -> Result<(), io::Error> {
+    let oldest = format!("{}.{}", path, ROTATED_LOGS);
+    if Path::new(&oldest).exists() {
+        fs::remove_file(&oldest)?;
+    }
+
+    for n in (1..ROTATED_LOGS).rev() {
+        let from = format!("{}.{}", path, n);
+        if Path::new(&from).exists() {
+            fs::rename(&from, format!("{}.{}", path, n + 1))?;
+        }
+    }
+
+    fs::rename(path, format!("{}.1", path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::process;
+
+    #[test]
+    fn rotates_log_files() {
+        let dir = env::temp_dir().join(format!("saml2aws-auto-logs-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = format!("{}", dir.join("saml2aws-auto.log").display());
+
+        for n in 0..=ROTATED_LOGS {
+            fs::write(&path, n.to_string()).unwrap();
+            rotate(&path).unwrap();
+        }
+
+        assert!(!Path::new(&path).exists());
+        assert_eq!(
+            fs::read_to_string(format!("{}.1", path)).unwrap(),
+            ROTATED_LOGS.to_string()
+        );
+        assert_eq!(
+            fs::read_to_string(format!("{}.{}", path, ROTATED_LOGS)).unwrap(),
+            "1"
+        );
+        assert!(!Path::new(&format!("{}.{}", path, ROTATED_LOGS + 1)).exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,528 @@
+use clap::{ArgAction, Args, Parser, Subcommand};
+
+pub mod configure;
+pub mod exec;
+pub mod groups;
+pub mod logging;
+mod picker;
+pub mod refresh;
+pub mod roles;
+pub mod self_update;
+pub mod status;
+pub mod update;
+
+use config::{parse_account, parse_duration, Account, Storage};
+use saml2aws_auto::Secret;
+
+#[derive(Parser)]
+#[command(author, version, about, long_about = None)]
+#[command(propagate_version = true)]
+pub struct Cli {
+    /// Sets the level of verbosity
+    #[arg(short, long)]
+    pub verbose: bool,
+
+    /// Sets a custom config file
+    #[arg(short, long, value_name = "FILE", env = "SAML2AWS_AUTO_CONFIG")]
+    pub config: Option<String>,
+
+    /// Writes a JSON log of the run to the file, regardless of --verbose
+    #[arg(long, value_name = "FILE", env = "SAML2AWS_AUTO_LOG_FILE")]
+    pub log_file: Option<String>,
+
+    /// PEM file with additional CA certificates to trust
+    #[arg(long, value_name = "FILE", env = "SAML2AWS_AUTO_CA_BUNDLE")]
+    pub ca_bundle: Option<String>,
+
+    /// PEM file with a client certificate (and optionally its key) for mutual TLS
+    #[arg(long, value_name = "FILE")]
+    pub client_cert: Option<String>,
+
+    /// PEM file with the private key of the client certificate
+    #[arg(long, value_name = "FILE", requires = "client_cert")]
+    pub client_key: Option<String>,
+
+    /// PKCS#12 file with a client certificate and key for mutual TLS
+    #[arg(long, value_name = "FILE", conflicts_with = "client_cert")]
+    pub client_pkcs12: Option<String>,
+
+    /// Accept invalid TLS certificates. Only use this with local test IdPs.
+    #[arg(long)]
+    pub insecure: bool,
+
+    /// Skip using the password manager (for unsupported platforms)
+    #[arg(long)]
+    pub skip_password_manager: bool,
+
+    #[command(subcommand)]
+    pub command: Commands,
+}
+
+#[derive(Subcommand)]
+pub enum Commands {
+    /// Configure saml2aws-auto
+    Configure {
+        /// Configure the named IdP instead of the default one
+        #[arg(long)]
+        idp: Option<String>,
+    },
+
+    /// Manage groups
+    Groups {
+        #[command(subcommand)]
+        command: GroupCommands,
+    },
+
+    /// Refresh credentials for a group
+    Refresh(RefreshArgs),
+
+    /// Inspect the roles available from the IdP
+    Roles {
+        #[command(subcommand)]
+        command: RolesCommands,
+    },
+
+    /// Show the session status of groups
+    Status(StatusArgs),
+
+    /// Run a command with the credentials of a profile in its environment
+    Exec(ExecArgs),
+
+    /// Print the credentials of a profile for the credential_process setting of the AWS CLI
+    CredentialProcess {
+        /// Name of the profile
+        profile: String,
+    },
+
+    /// Print version info and exit
+    Version,
+
+    /// Replace this binary with the latest (or another) release
+    ///
+    /// Without `update_check.public_key` in the config file, the download is only checked
+    /// against the SHA256SUMS file of the same release. That detects corrupted downloads,
+    /// but trusts the release source as much as the binary. Configure the public key of
+    /// the release signing key to also verify the signature of the checksums.
+    #[command(disable_version_flag = true)]
+    SelfUpdate(SelfUpdateArgs),
+}
+
+impl Commands {
+    /// Returns true if the output of the command is consumed by other programs, e.g. as
+    /// JSON. Nothing else may be printed to stdout then.
+    pub fn prints_data(&self) -> bool {
+        match *self {
+            Commands::Exec(_) | Commands::CredentialProcess { .. } => true,
+            Commands::Roles {
+                command: RolesCommands::List(ref args),
+            } => args.json,
+            Commands::Groups {
+                command: GroupCommands::Export { .. },
+            } => true,
+            _ => false,
+        }
+    }
+}
+
+#[derive(Subcommand)]
+#[allow(clippy::large_enum_variant)]
+pub enum GroupCommands {
+    /// Add a new group
+    Add(AddGroupArgs),
+
+    /// Delete a group
+    Delete {
+        /// Name of the group to delete
+        group: String,
+    },
+
+    /// List all groups
+    List,
+
+    /// Show the configuration and sessions of a group
+    Show {
+        /// Name of the group to show
+        group: String,
+    },
+
+    /// Change the settings or accounts of a group without logging in
+    Edit(EditGroupArgs),
+
+    /// Override the settings of the group for a single account
+    EditAccount(EditAccountArgs),
+
+    /// Print the definitions of groups as YAML, without session state
+    Export {
+        /// Names of the groups to export (defaults to all groups)
+        groups: Vec<String>,
+    },
+
+    /// Import group definitions from a file created with export
+    Import {
+        /// File to import the groups from
+        file: String,
+
+        /// Append accounts to existing groups instead of replacing them
+        #[arg(long)]
+        append: bool,
+    },
+
+    /// Create a group from the accounts of other groups
+    Compose {
+        /// Name of the composite group
+        group: String,
+
+        /// Names of the groups to include
+        #[arg(required = true)]
+        groups: Vec<String>,
+    },
+
+    /// Rename a group
+    Rename {
+        /// Name of the group to rename
+        group: String,
+
+        /// New name of the group
+        new_name: String,
+    },
+}
+
+#[derive(Args)]
+pub struct AddGroupArgs {
+    /// Name of the group
+    pub name: String,
+
+    /// AWS Role name to assume, can be repeated. If an account has several of the roles,
+    /// the first one given is used. Required when selecting accounts with flags
+    #[arg(short, long = "role", value_name = "ROLE", action = ArgAction::Append)]
+    pub roles: Vec<String>,
+
+    /// Regular expression matching the AWS Role names to assume
+    #[arg(long, value_name = "REGEX")]
+    pub role_match: Option<String>,
+
+    /// Prefix of AWS account names to add to the group. If no account filter is given,
+    /// the accounts can be picked interactively
+    #[arg(short, long)]
+    pub prefix: Option<String>,
+
+    /// Specific AWS accounts to add to the group
+    #[arg(short, long, num_args = 1..)]
+    pub accounts: Option<Vec<String>>,
+
+    /// Specific AWS account id to add to the group, can be repeated
+    #[arg(long = "account-id", value_name = "ID", action = ArgAction::Append)]
+    pub account_ids: Option<Vec<String>>,
+
+    /// Regular expression the AWS account names must match
+    #[arg(long = "match", value_name = "REGEX")]
+    pub matches: Option<String>,
+
+    /// Regular expression of AWS account names to leave out
+    #[arg(long, value_name = "REGEX")]
+    pub exclude: Option<String>,
+
+    /// Append accounts to an existing group
+    #[arg(long)]
+    pub append: bool,
+
+    /// Session duration in seconds
+    #[arg(short = 'd', long)]
+    pub session_duration: Option<i64>,
+
+    /// STS endpoint to use
+    #[arg(long)]
+    pub sts_endpoint: Option<String>,
+
+    /// Refresh accounts with less remaining session lifetime than this (e.g. 15m)
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+    pub min_remaining: Option<i64>,
+
+    /// Where to store the credentials of the group
+    #[arg(long, value_enum, default_value_t = Storage::Plaintext)]
+    pub storage: Storage,
+
+    /// Name of the IdP to log in with (defaults to the IdP configured first)
+    #[arg(long)]
+    pub idp: Option<String>,
+
+    /// IDP Username
+    #[arg(short, long)]
+    pub username: Option<String>,
+
+    /// IDP Password
+    #[arg(short = 'P', long)]
+    pub password: Option<Secret>,
+
+    /// MFA Token
+    #[arg(short, long)]
+    pub mfa: Option<Secret>,
+}
+
+#[derive(Args)]
+pub struct EditGroupArgs {
+    /// Name of the group to edit
+    pub group: String,
+
+    /// Session duration in seconds
+    #[arg(short = 'd', long, conflicts_with = "unset_session_duration")]
+    pub session_duration: Option<i64>,
+
+    /// Remove the session duration, using the one of the IdP instead
+    #[arg(long)]
+    pub unset_session_duration: bool,
+
+    /// STS endpoint to use
+    #[arg(long, conflicts_with = "unset_sts_endpoint")]
+    pub sts_endpoint: Option<String>,
+
+    /// Remove the STS endpoint, using the default one instead
+    #[arg(long)]
+    pub unset_sts_endpoint: bool,
+
+    /// AWS region to write to the profiles. Also selects the regional STS endpoint
+    #[arg(long, conflicts_with = "unset_region")]
+    pub region: Option<String>,
+
+    /// Remove the AWS region
+    #[arg(long)]
+    pub unset_region: bool,
+
+    /// Template for profile names. Supports {group}, {account}, {account_id} and {role}
+    #[arg(long, conflicts_with = "unset_profile_template")]
+    pub profile_template: Option<String>,
+
+    /// Remove the profile template, using the account names as profile names
+    #[arg(long)]
+    pub unset_profile_template: bool,
+
+    /// Refresh accounts with less remaining session lifetime than this (e.g. 15m)
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration, conflicts_with = "unset_min_remaining")]
+    pub min_remaining: Option<i64>,
+
+    /// Remove the minimum remaining session lifetime
+    #[arg(long)]
+    pub unset_min_remaining: bool,
+
+    /// Name of the IdP to log in with
+    #[arg(long, conflicts_with = "unset_idp")]
+    pub idp: Option<String>,
+
+    /// Log in with the default IdP
+    #[arg(long)]
+    pub unset_idp: bool,
+
+    /// Where to store the credentials of the group
+    #[arg(long, value_enum)]
+    pub storage: Option<Storage>,
+
+    /// Add an account to the group
+    #[arg(long = "add-account", value_name = "NAME=ARN", value_parser = parse_account)]
+    pub add_accounts: Vec<Account>,
+
+    /// Remove an account from the group
+    #[arg(long = "remove-account", value_name = "NAME")]
+    pub remove_accounts: Vec<String>,
+}
+
+#[derive(Args)]
+pub struct EditAccountArgs {
+    /// Name of the group
+    pub group: String,
+
+    /// Name of the account to edit
+    pub account: String,
+
+    /// Session duration in seconds
+    #[arg(short = 'd', long, conflicts_with = "unset_session_duration")]
+    pub session_duration: Option<i64>,
+
+    /// Remove the session duration, using the one of the group instead
+    #[arg(long)]
+    pub unset_session_duration: bool,
+
+    /// STS endpoint to use
+    #[arg(long, conflicts_with = "unset_sts_endpoint")]
+    pub sts_endpoint: Option<String>,
+
+    /// Remove the STS endpoint, using the one of the group instead
+    #[arg(long)]
+    pub unset_sts_endpoint: bool,
+
+    /// AWS region to write to the profile. Also selects the regional STS endpoint
+    #[arg(long, conflicts_with = "unset_region")]
+    pub region: Option<String>,
+
+    /// Remove the AWS region, using the one of the group instead
+    #[arg(long)]
+    pub unset_region: bool,
+
+    /// Name of the profile to write the credentials to
+    #[arg(long, conflicts_with = "unset_profile")]
+    pub profile: Option<String>,
+
+    /// Remove the profile name, using the profile template of the group instead
+    #[arg(long)]
+    pub unset_profile: bool,
+
+    /// Skip the account when refreshing the group
+    #[arg(long, conflicts_with = "enable")]
+    pub disable: bool,
+
+    /// Refresh the account again when refreshing the group
+    #[arg(long)]
+    pub enable: bool,
+}
+
+#[derive(Subcommand)]
+pub enum RolesCommands {
+    /// List all account/role pairs available from the IdP
+    List(ListRolesArgs),
+}
+
+#[derive(Args)]
+pub struct ListRolesArgs {
+    /// Only list accounts whose name starts with this prefix
+    #[arg(short, long)]
+    pub prefix: Option<String>,
+
+    /// Only list roles with this name
+    #[arg(short, long)]
+    pub role: Option<String>,
+
+    /// Only list roles of the account with this id
+    #[arg(long)]
+    pub account_id: Option<String>,
+
+    /// Print the roles as JSON
+    #[arg(long)]
+    pub json: bool,
+
+    /// Name of the IdP to log in with (defaults to the IdP configured first)
+    #[arg(long)]
+    pub idp: Option<String>,
+
+    /// IDP Username
+    #[arg(short, long)]
+    pub username: Option<String>,
+
+    /// IDP Password
+    #[arg(short = 'P', long)]
+    pub password: Option<Secret>,
+
+    /// MFA Token
+    #[arg(short, long)]
+    pub mfa: Option<Secret>,
+}
+
+#[derive(Args)]
+pub struct RefreshArgs {
+    /// Groups to refresh
+    pub groups: Vec<String>,
+
+    /// Force refresh of credentials
+    #[arg(short, long)]
+    pub force: bool,
+
+    /// Refresh accounts with less remaining session lifetime than this (e.g. 15m).
+    /// Overrides the setting of the group
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+    pub min_remaining: Option<i64>,
+
+    /// IDP Username
+    #[arg(short, long)]
+    pub username: Option<String>,
+
+    /// IDP Password
+    #[arg(short = 'P', long)]
+    pub password: Option<Secret>,
+
+    /// MFA Token
+    #[arg(short, long)]
+    pub mfa: Option<Secret>,
+}
+
+#[derive(Args)]
+pub struct StatusArgs {
+    /// Groups to check (defaults to all groups)
+    pub groups: Vec<String>,
+
+    /// Minutes of remaining session lifetime below which a session is considered expiring
+    #[arg(short = 'w', long, value_name = "MINUTES", default_value_t = 15, value_parser = clap::value_parser!(i64).range(0..=10080))]
+    pub expiring_within: i64,
+
+    /// Treat sessions with less remaining lifetime than this as expiring (e.g. 15m), like
+    /// refresh does. Overrides the setting of the group
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+    pub min_remaining: Option<i64>,
+
+    /// Read expiration times from the AWS credentials file instead of the config
+    #[arg(long)]
+    pub credentials_file: bool,
+}
+
+#[derive(Args)]
+pub struct ExecArgs {
+    /// Name of the profile whose credentials are used
+    pub profile: String,
+
+    /// Command to run, followed by its arguments
+    #[arg(last = true, required = true)]
+    pub command: Vec<String>,
+}
+
+#[derive(Args)]
+pub struct SelfUpdateArgs {
+    /// Only check whether a newer release is available
+    #[arg(long)]
+    pub check: bool,
+
+    /// Install the given release instead of the latest one, e.g. to downgrade
+    #[arg(long, value_name = "VERSION")]
+    pub version: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roles_take_one_value_per_flag() {
+        let cli = Cli::try_parse_from([
+            "saml2aws-auto",
+            "groups",
+            "add",
+            "-r",
+            "Administrator",
+            "my-group",
+            "--role",
+            "ReadOnly",
+        ])
+        .unwrap();
+
+        match cli.command {
+            Commands::Groups {
+                command: GroupCommands::Add(args),
+            } => {
+                assert_eq!(args.name, "my-group");
+                assert_eq!(args.roles, vec!["Administrator", "ReadOnly"]);
+            }
+            _ => panic!("expected groups add"),
+        }
+    }
+
+    #[test]
+    fn commands_printing_data() {
+        let prints_data = |args: &[&str]| Cli::try_parse_from(args).unwrap().command.prints_data();
+
+        assert!(prints_data(&["saml2aws-auto", "roles", "list", "--json"]));
+        assert!(prints_data(&[
+            "saml2aws-auto",
+            "credential-process",
+            "prod"
+        ]));
+        assert!(prints_data(&["saml2aws-auto", "groups", "export"]));
+        assert!(!prints_data(&["saml2aws-auto", "roles", "list"]));
+        assert!(!prints_data(&["saml2aws-auto", "status"]));
+    }
+}
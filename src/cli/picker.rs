@@ -0,0 +1,240 @@
+use std::collections::HashSet;
+use std::io;
+use std::io::prelude::*;
+
+use crossterm::cursor::{Hide, MoveTo, Show};
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::style::Stylize;
+use crossterm::terminal::{
+    self, disable_raw_mode, enable_raw_mode, Clear, ClearType, EnterAlternateScreen,
+    LeaveAlternateScreen,
+};
+use crossterm::{execute, queue};
+
+use saml2aws_auto::RoleInfo;
+
+/// Number of lines used by the header above the list of roles
+const HEADER_LINES: u16 = 4;
+
+struct Picker<'a> {
+    title: &'a str,
+    roles: &'a [RoleInfo],
+    query: String,
+    selected: HashSet<usize>,
+    cursor: usize,
+    offset: usize,
+}
+
+/// Opens an interactive, searchable list of roles in the terminal and lets the user
+/// select the roles to use. Only one role can be selected per account, since accounts
+/// are identified by their name in the AWS credentials file.
+///
+/// Returns `None` if the user cancelled the selection.
+pub fn pick_roles(title: &str, roles: &[RoleInfo]) -> io::Result<Option<Vec<RoleInfo>>> {
+    let mut stdout = io::stdout();
+
+    enable_raw_mode()?;
+    let guard = TerminalGuard;
+    execute!(stdout, EnterAlternateScreen, Hide)?;
+
+    let mut picker = Picker {
+        title,
+        roles,
+        query: String::new(),
+        selected: HashSet::new(),
+        cursor: 0,
+        offset: 0,
+    };
+    let result = picker.run(&mut stdout);
+    drop(guard);
+
+    let confirmed = result?;
+
+    if !confirmed {
+        return Ok(None);
+    }
+
+    let mut selected: Vec<usize> = picker.selected.into_iter().collect();
+    selected.sort();
+
+    Ok(Some(
+        selected.into_iter().map(|i| roles[i].clone()).collect(),
+    ))
+}
+
+/// Restores the terminal when it is dropped, also if rendering or reading keys failed
+struct TerminalGuard;
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        // Both steps run even if one of them fails
+        let _ = execute!(io::stdout(), Show, LeaveAlternateScreen);
+        let _ = disable_raw_mode();
+    }
+}
+
+impl<'a> Picker<'a> {
+    fn run(&mut self, stdout: &mut io::Stdout) -> io::Result<bool> {
+        loop {
+            self.render(stdout)?;
+
+            let key = match event::read()? {
+                Event::Key(key) if key.kind != KeyEventKind::Release => key,
+                _ => continue,
+            };
+
+            match key {
+                KeyEvent {
+                    code: KeyCode::Char('c'),
+                    modifiers: KeyModifiers::CONTROL,
+                    ..
+                }
+                | KeyEvent {
+                    code: KeyCode::Esc, ..
+                } => return Ok(false),
+                KeyEvent {
+                    code: KeyCode::Enter,
+                    ..
+                } => return Ok(true),
+                KeyEvent {
+                    code: KeyCode::Up, ..
+                } => self.cursor = self.cursor.saturating_sub(1),
+                KeyEvent {
+                    code: KeyCode::Down,
+                    ..
+                } if self.cursor + 1 < self.visible().len() => self.cursor += 1,
+                KeyEvent {
+                    code: KeyCode::Tab, ..
+                } => self.toggle(),
+                KeyEvent {
+                    code: KeyCode::Backspace,
+                    ..
+                } => {
+                    self.query.pop();
+                    self.cursor = 0;
+                }
+                KeyEvent {
+                    code: KeyCode::Char(c),
+                    modifiers,
+                    ..
+                } if modifiers.difference(KeyModifiers::SHIFT).is_empty() => {
+                    self.query.push(c);
+                    self.cursor = 0;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Returns the indices of all roles matching the current query
+    fn visible(&self) -> Vec<usize> {
+        (0..self.roles.len())
+            .filter(|&i| matches(&self.roles[i], &self.query))
+            .collect()
+    }
+
+    fn toggle(&mut self) {
+        let index = match self.visible().get(self.cursor) {
+            Some(&i) => i,
+            None => return,
+        };
+
+        if self.selected.remove(&index) {
+            return;
+        }
+
+        // Deselect other roles of the same account
+        let account_id = &self.roles[index].account_id;
+        let roles = self.roles;
+        self.selected
+            .retain(|&i| &roles[i].account_id != account_id);
+        self.selected.insert(index);
+    }
+
+    fn render(&mut self, stdout: &mut io::Stdout) -> io::Result<()> {
+        let (_, height) = terminal::size()?;
+        let rows = height.saturating_sub(HEADER_LINES).max(1) as usize;
+
+        if self.cursor < self.offset {
+            self.offset = self.cursor;
+        } else if self.cursor >= self.offset + rows {
+            self.offset = self.cursor + 1 - rows;
+        }
+
+        queue!(stdout, Clear(ClearType::All), MoveTo(0, 0))?;
+        write!(
+            stdout,
+            "{}\r\n{}\r\n\r\n{} {}\r\n",
+            self.title.yellow(),
+            "Type to search, tab to select, enter to confirm, esc to cancel".dark_grey(),
+            "?".green(),
+            self.query,
+        )?;
+
+        for (line, &index) in self
+            .visible()
+            .iter()
+            .enumerate()
+            .skip(self.offset)
+            .take(rows)
+        {
+            let role = &self.roles[index];
+            let checkbox = if self.selected.contains(&index) {
+                "[x]".green().to_string()
+            } else {
+                "[ ]".to_string()
+            };
+            let text = format!(
+                "{} {} ({}) {}",
+                checkbox,
+                role.account_name,
+                role.account_id,
+                role.role_name.as_str().blue()
+            );
+
+            if line == self.cursor {
+                write!(stdout, "{} {}\r\n", ">".yellow(), text)?;
+            } else {
+                write!(stdout, "  {}\r\n", text)?;
+            }
+        }
+
+        stdout.flush()
+    }
+}
+
+/// Returns true if the account name, id or role name contain every word of the query
+fn matches(role: &RoleInfo, query: &str) -> bool {
+    let haystack = format!(
+        "{} {} {}",
+        role.account_name, role.account_id, role.role_name
+    )
+    .to_lowercase();
+
+    query
+        .to_lowercase()
+        .split_whitespace()
+        .all(|word| haystack.contains(word))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn role() -> RoleInfo {
+        RoleInfo {
+            account_name: "payments-prod".into(),
+            account_id: "123456789012".into(),
+            role_name: "Administrator".into(),
+            arn: "arn:aws:iam::123456789012:role/Administrator".into(),
+        }
+    }
+
+    #[test]
+    fn matches_every_word_case_insensitive() {
+        assert!(matches(&role(), ""));
+        assert!(matches(&role(), "PROD admin"));
+        assert!(matches(&role(), "1234"));
+        assert!(!matches(&role(), "prod readonly"));
+    }
+}
@@ -0,0 +1,332 @@
+use chrono::prelude::*;
+use tabled::settings::object::Columns;
+use tabled::settings::{Alignment, Modify, Style};
+use tabled::{Table, Tabled};
+
+use cli::configure::prompt;
+use crossterm::style::Stylize;
+use saml2aws_auto::{
+    refresh_group, sessions_valid, span, GroupOutcome, GroupRefresh, RefreshOptions, Secret,
+    Session,
+};
+
+use config;
+use config::Storage;
+
+use crate::cli::RefreshArgs;
+
+pub fn command(cfg: &mut config::Config, args: &RefreshArgs) {
+    // Composite groups are refreshed through the groups they include
+    let groups = match cfg.resolve_groups(&args.groups) {
+        Ok(groups) => groups,
+        Err(e) => {
+            println!("\nCould not refresh:\n\n\t{}\n", e.red());
+            return;
+        }
+    };
+
+    let options = RefreshOptions {
+        force: args.force,
+        min_remaining: args.min_remaining,
+    };
+
+    // If all credentials are valid, skip refresh entirely
+    if sessions_valid(cfg, &options, &groups) && !args.force {
+        println!("Nothing to refresh, all credentials are valid. Use --force to overwrite.");
+        return;
+    }
+
+    // Groups are refreshed with one login per IdP
+    let by_idp = cfg.groups_by_idp(&groups);
+    let mut logins: Vec<&(Option<String>, Vec<String>)> = vec![];
+
+    for entry in &by_idp {
+        let (_, ref idp_groups) = *entry;
+
+        if by_idp.len() > 1 && sessions_valid(cfg, &options, idp_groups) && !args.force {
+            println!(
+                "Nothing to refresh for groups {}, all credentials are valid.",
+                idp_groups.join(", ").yellow()
+            );
+            continue;
+        }
+
+        logins.push(entry);
+    }
+
+    // Credentials given on the command line can only belong to one IdP
+    if logins.len() > 1
+        && (args.username.is_some() || args.password.is_some() || args.mfa.is_some())
+    {
+        println!(
+            "\nCould not refresh:\n\n\t{}\n",
+            "The --username, --password and --mfa flags cannot be used with groups of several IdPs, refresh them separately".red()
+        );
+        return;
+    }
+
+    for (idp, idp_groups) in logins {
+        refresh_idp(cfg, args, &options, idp.as_deref(), idp_groups);
+    }
+
+    cfg.save().unwrap();
+}
+
+/// Logs in to the IdP once and refreshes the accounts of the groups
+fn refresh_idp(
+    cfg: &mut config::Config,
+    args: &RefreshArgs,
+    options: &RefreshOptions,
+    idp_name: Option<&str>,
+    groups: &[String],
+) {
+    let _span = span(&format!("idp={}", idp_name.unwrap_or("default")));
+
+    let idp = match cfg.idp(idp_name) {
+        Some(idp) => idp,
+        None => {
+            println!(
+                "\nCould not refresh groups {}:\n\n\t{}\n",
+                groups.join(", ").yellow(),
+                format!("The IdP {} does not exist", idp_name.unwrap_or_default()).red()
+            );
+            return;
+        }
+    };
+
+    let username = match args.username.as_deref().or(idp.username.as_deref()) {
+        Some(username) => username,
+        None => {
+            println!(
+                "\nCould not refresh groups {}:\n\n\t{}\n",
+                groups.join(", ").yellow(),
+                "No username configured, pass --username or run saml2aws-auto configure".red()
+            );
+            return;
+        }
+    };
+
+    let password = match args
+        .password
+        .clone()
+        .map_or_else(|| idp.resolve_password(), Ok)
+    {
+        Ok(p) => p,
+        Err(e) => {
+            println!(
+                "\nCould not refresh groups {}:\n\n\t{}\n",
+                groups.join(", ").yellow(),
+                e.red()
+            );
+            return;
+        }
+    };
+
+    let mfa = match args.mfa {
+        Some(ref m) => m.clone(),
+        None => {
+            debug!("mfa flag not set, no valid session");
+            let question = match idp_name {
+                Some(name) => format!("MFA Token for {}", name),
+                None => "MFA Token".into(),
+            };
+            Secret::from(prompt(&question, Some("000000"), false).unwrap())
+        }
+    };
+
+    // Groups with selectors are synchronised with the roles of the IdP
+    let synchronise = cfg
+        .groups
+        .iter()
+        .filter(|(name, _)| groups.contains(name))
+        .any(|(_, group)| !group.selectors.is_empty());
+
+    let mut session = match Session::login(&idp, username, password, mfa, synchronise) {
+        Ok(session) => session,
+        Err(e) => {
+            if e.kind.is_unrecoverable() {
+                println!(
+                    "\n{} Cannot recover from error:\n\n\t{}\n",
+                    "!".red(),
+                    e.to_string().red(),
+                );
+            } else {
+                println!(
+                    "\nCould not refresh groups {}:\n\n\t{}\n",
+                    groups.join(", ").yellow(),
+                    e.to_string().red()
+                );
+            }
+            return;
+        }
+    };
+
+    if let Some(Err(ref e)) = session.roles {
+        error!("{:?}", e);
+        println!(
+            "{}: Could not list the roles of your account, groups will not be synchronised: {}",
+            "WARNING".yellow(),
+            e
+        );
+    }
+
+    for group_name in groups {
+        match refresh_group(cfg, &mut session, group_name, options) {
+            Ok(result) => print_result(&cfg.groups[group_name], group_name, result),
+            Err(e) => println!(
+                "\nCould not refresh group {}:\n\n\t{}\n",
+                group_name.as_str().yellow(),
+                e.to_string().red()
+            ),
+        }
+    }
+}
+
+fn print_result(group: &config::Group, group_name: &str, result: GroupRefresh) {
+    let GroupRefresh {
+        added,
+        removed,
+        sync_error,
+        outcome,
+    } = result;
+
+    if let Some(e) = sync_error {
+        println!(
+            "{}: Could not synchronise group {}: {}",
+            "WARNING".yellow(),
+            group_name.yellow(),
+            e
+        );
+    }
+
+    let results = match outcome {
+        GroupOutcome::Refreshed(results) => results,
+        outcome => {
+            if !removed.is_empty() {
+                println!(
+                    "Removed accounts from group {} that no longer match its selectors: {}",
+                    group_name.yellow(),
+                    removed.join(", ")
+                );
+            }
+
+            match outcome {
+                // Composite groups usually only consist of the groups they include
+                GroupOutcome::Empty if group.includes.is_empty() => {
+                    println!("Nothing to refresh. Group {} is empty.", group_name.yellow())
+                }
+                GroupOutcome::AllDisabled => println!(
+                    "Nothing to refresh. All accounts of group {} are disabled.",
+                    group_name.yellow(),
+                ),
+                GroupOutcome::Valid => println!(
+                    "Nothing to refresh. All accounts have valid sessions. Use --force to overwrite."
+                ),
+                _ => {}
+            }
+            return;
+        }
+    };
+
+    // Failed logins are reported before the table, other errors only in it
+    for e in results.iter().filter_map(|r| r.as_ref().err()) {
+        if let Some(ref kind) = e.kind {
+            println!("{} {}", e.account_name, "FAIL".red());
+
+            if kind.is_unrecoverable() {
+                println!(
+                    "\n{} Cannot recover from error:\n\n\t{}\n",
+                    "!".red(),
+                    e.to_string().red(),
+                );
+            }
+        }
+    }
+
+    #[derive(Debug, Tabled)]
+    struct TableRefreshedAccount {
+        #[tabled(rename = "Account Name")]
+        account_name: String,
+        #[tabled(rename = "Refreshed")]
+        refreshed: String,
+        #[tabled(rename = "Result")]
+        expiration: String,
+    }
+
+    let outputs: Vec<TableRefreshedAccount> = results
+        .into_iter()
+        .map(|res| match res {
+            Ok(output) => {
+                let now = Local::now();
+
+                let expiration = format!(
+                    "valid for {} minutes",
+                    format!(
+                        "{}",
+                        output
+                            .account
+                            .valid_until
+                            .unwrap()
+                            .signed_duration_since(now)
+                            .num_minutes()
+                    )
+                    .green()
+                );
+
+                TableRefreshedAccount {
+                    account_name: output.account.name,
+                    refreshed: if output.renewed {
+                        "✓".green().to_string()
+                    } else {
+                        "⨯".bold().red().to_string()
+                    },
+                    expiration,
+                }
+            }
+            Err(ref e) => TableRefreshedAccount {
+                account_name: e.account_name.clone(),
+                refreshed: "⨯".bold().red().to_string(),
+                expiration: e.to_string().red().to_string(),
+            },
+        })
+        .map(|row| match added.contains(&row.account_name) {
+            true => TableRefreshedAccount {
+                account_name: format!("{} {}", row.account_name, "(added)".green()),
+                ..row
+            },
+            false => row,
+        })
+        .chain(removed.iter().map(|name| TableRefreshedAccount {
+            account_name: format!("{} {}", name, "(removed)".yellow()),
+            refreshed: "-".to_string(),
+            expiration: "no longer part of the group".to_string(),
+        }))
+        .collect();
+
+    print!(
+        "\n\n{}",
+        Table::new(outputs)
+            .with(Style::modern())
+            .with(Modify::new(Columns::single(1)).with(Alignment::center()))
+            .to_string()
+    );
+
+    let example_account = group.profile_name(group_name, group.enabled_accounts().next().unwrap());
+
+    match group.storage {
+        Storage::Plaintext => {
+            println!("\nRefreshed group {}. To use them in the AWS cli, apply the --profile flag with the name of the account.", group_name.yellow());
+            println!(
+                "\nExample:\n\n\taws --profile {} s3 ls\n",
+                example_account.as_str().yellow(),
+            );
+        }
+        Storage::Encrypted => {
+            println!("\nRefreshed group {}. The credentials are stored encrypted, use saml2aws-auto exec or credential-process with the name of the account.", group_name.yellow());
+            println!(
+                "\nExample:\n\n\tsaml2aws-auto exec {} -- aws s3 ls\n",
+                example_account.as_str().yellow(),
+            );
+        }
+    }
+}
@@ -0,0 +1,132 @@
+use std::io;
+use std::io::prelude::*;
+
+use crossterm::style::Stylize;
+use serde_json;
+use tabled::settings::Style;
+use tabled::{Table, Tabled};
+
+use cli::configure::{prompt, prompt_to};
+use config;
+use saml2aws_auto::{fetch_roles, RoleInfo, Secret};
+
+use crate::cli::{ListRolesArgs, RolesCommands};
+
+/// Runs the command, returning false if it failed
+pub fn command(cfg: &config::Config, command: &RolesCommands) -> bool {
+    match command {
+        RolesCommands::List(args) => list(cfg, args),
+    }
+}
+
+/// Prints the error, as a JSON object if JSON output was requested
+fn fail(args: &ListRolesArgs, message: &str) -> bool {
+    if args.json {
+        println!("{}", serde_json::json!({ "error": message }));
+    } else {
+        println!("\nCould not list roles:\n\n\t{}\n", message.red());
+    }
+    false
+}
+
+fn list(cfg: &config::Config, args: &ListRolesArgs) -> bool {
+    let idp = match cfg.idp(args.idp.as_deref()) {
+        Some(idp) => idp,
+        None => return fail(args, "The specified IdP does not exist"),
+    };
+
+    let username = match args.username.as_deref().or(idp.username.as_deref()) {
+        Some(username) => username,
+        None => {
+            return fail(
+                args,
+                "No username configured, pass --username or run saml2aws-auto configure",
+            )
+        }
+    };
+
+    let password = match args
+        .password
+        .clone()
+        .map_or_else(|| idp.resolve_password(), Ok)
+    {
+        Ok(p) => p,
+        Err(e) => return fail(args, &e),
+    };
+
+    let mfa = match args.mfa.clone().or_else(|| {
+        // Only the JSON document is written to stdout, so it can be piped
+        let token = match args.json {
+            true => prompt_to(&mut io::stderr(), "MFA Token", Some("000000"), false),
+            false => prompt("MFA Token", Some("000000"), false),
+        };
+        token.map(Secret::from)
+    }) {
+        Some(mfa) => mfa,
+        None => return fail(args, "No MFA Token provided"),
+    };
+
+    if !args.json {
+        print!("Listing allowed roles for your account\t");
+        io::stdout().flush().unwrap();
+    }
+
+    let roles = match fetch_roles(&idp, username, &password, &mfa) {
+        Ok(r) => r,
+        Err(e) => {
+            trace!("command.fetch_roles.err");
+            error!("{:?}", e);
+            if !args.json {
+                println!("{}", "FAIL".red());
+            }
+            return fail(args, &e.to_string());
+        }
+    };
+
+    let roles: Vec<RoleInfo> = roles
+        .into_iter()
+        .filter(|r| match &args.prefix {
+            Some(prefix) => r.account_name.starts_with(prefix),
+            None => true,
+        })
+        .filter(|r| match &args.role {
+            Some(role) => &r.role_name == role,
+            None => true,
+        })
+        .filter(|r| match &args.account_id {
+            Some(account_id) => &r.account_id == account_id,
+            None => true,
+        })
+        .collect();
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&roles).unwrap());
+        return true;
+    }
+
+    println!("{}", "SUCCESS".green());
+
+    if roles.is_empty() {
+        println!("\nNo roles were found with the given filters.");
+        return true;
+    }
+
+    #[derive(Debug, Tabled)]
+    struct TableRole {
+        #[tabled(rename = "Account Name")]
+        account_name: String,
+        #[tabled(rename = "Account ID")]
+        account_id: String,
+        #[tabled(rename = "Role")]
+        role_name: String,
+    }
+
+    let rows = roles.into_iter().map(|r| TableRole {
+        account_name: r.account_name,
+        account_id: r.account_id,
+        role_name: r.role_name,
+    });
+
+    println!("\n{}", Table::new(rows).with(Style::modern()));
+    true
+}
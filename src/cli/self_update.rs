@@ -0,0 +1,326 @@
+use std::env;
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{self, Cursor, Read, Write};
+use std::path::Path;
+use std::process::Command;
+use std::time::Duration;
+
+use crossterm::style::Stylize;
+use openssl::hash::MessageDigest;
+use openssl::pkey::PKey;
+use openssl::sha::sha256;
+use openssl::sign::Verifier;
+use zip::ZipArchive;
+
+use cli::update;
+use cli::SelfUpdateArgs;
+use config::UpdateCheckConfig;
+use saml2aws_auto::send_with_retries;
+
+/// Release asset listing the SHA256 checksums of the other assets, as written by `sha256sum`
+const CHECKSUMS: &str = "SHA256SUMS";
+/// Release asset with the signature of the checksums
+const SIGNATURE: &str = "SHA256SUMS.sig";
+
+/// Name of the binary inside of the release archive
+#[cfg(windows)]
+const BINARY: &str = "saml2aws-auto.exe";
+#[cfg(not(windows))]
+const BINARY: &str = "saml2aws-auto";
+
+/// Downloads can take longer than the usual request timeout on slow connections
+const DOWNLOAD_TIMEOUT: u64 = 300;
+
+pub fn command(args: &SelfUpdateArgs, settings: &UpdateCheckConfig) -> bool {
+    match run(args, settings) {
+        Ok(_) => true,
+        Err(e) => {
+            println!(
+                "\nCould not update saml2aws-auto:\n\n\t{}\n",
+                e.to_string().red()
+            );
+            false
+        }
+    }
+}
+
+fn run(args: &SelfUpdateArgs, settings: &UpdateCheckConfig) -> Result<(), Box<dyn Error>> {
+    let current = update::parse_version(env!("CARGO_PKG_VERSION"))?;
+    // Releases are tagged without a leading v
+    let tag = match args.version {
+        Some(ref v) => Some(update::parse_version(v)?.to_string()),
+        None => None,
+    };
+
+    let release = update::get_release(settings.url.as_deref(), tag.as_deref())?;
+    let version = update::parse_version(&release.tag_name)?;
+
+    if version == current || (tag.is_none() && version < current) {
+        println!("saml2aws-auto {} is up to date", current);
+        return Ok(());
+    }
+
+    if args.check {
+        println!(
+            "saml2aws-auto {} is available, {} is installed. Run {} to install it.",
+            version.to_string().green(),
+            current,
+            "saml2aws-auto self-update".yellow()
+        );
+        return Ok(());
+    }
+
+    let name = update::asset_name();
+    let asset = release.asset(&name).ok_or_else(|| {
+        format!(
+            "Release {} has no download for this platform ({})",
+            release.tag_name, name
+        )
+    })?;
+    let checksums = release.asset(CHECKSUMS).ok_or_else(|| {
+        format!(
+            "Release {} has no {} to verify the download with",
+            release.tag_name, CHECKSUMS
+        )
+    })?;
+
+    let checksums = download(&checksums.browser_download_url)?;
+    match settings.public_key {
+        Some(ref key) => {
+            let signature = release.asset(SIGNATURE).ok_or_else(|| {
+                format!(
+                    "Release {} is not signed ({} is missing)",
+                    release.tag_name, SIGNATURE
+                )
+            })?;
+            verify_signature(&checksums, &download(&signature.browser_download_url)?, key)?;
+        }
+        None => println!(
+            "{}: No public key is configured, the download is only verified with the checksums of the release",
+            "WARNING".yellow()
+        ),
+    }
+
+    println!("Downloading saml2aws-auto {}", version);
+    let archive = download(&asset.browser_download_url)?;
+    verify_checksum(&archive, &name, &String::from_utf8_lossy(&checksums))?;
+
+    let exe = env::current_exe()?.canonicalize()?;
+    replace(&exe, &extract(&archive)?).map_err(|e| match e.kind() {
+        io::ErrorKind::PermissionDenied => format!(
+            "Permission denied while replacing {}. Run the update as the owner of the file.",
+            exe.display()
+        ),
+        _ => format!("Could not replace {}: {}", exe.display(), e),
+    })?;
+
+    println!(
+        "Updated saml2aws-auto from {} to {}",
+        current,
+        version.to_string().green()
+    );
+    Ok(())
+}
+
+fn download(url: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+    debug!("downloading {}", url);
+
+    let request = update::client_builder()?
+        .build()?
+        .get(url)
+        .timeout(Duration::from_secs(DOWNLOAD_TIMEOUT));
+
+    Ok(send_with_retries(request)?
+        .error_for_status()?
+        .bytes()?
+        .to_vec())
+}
+
+/// Checks the data against its entry in a file in the format of `sha256sum`
+fn verify_checksum(data: &[u8], name: &str, checksums: &str) -> Result<(), String> {
+    let expected = checksums
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.split_whitespace();
+            Some((parts.next()?, parts.next()?.trim_start_matches('*')))
+        })
+        .find(|(_, file)| *file == name)
+        .map(|(checksum, _)| checksum.to_lowercase())
+        .ok_or_else(|| format!("{} does not contain a checksum of {}", CHECKSUMS, name))?;
+
+    let actual: String = sha256(data).iter().map(|b| format!("{:02x}", b)).collect();
+    if actual != expected {
+        return Err(format!(
+            "The checksum of {} is {}, but {} was expected",
+            name, actual, expected
+        ));
+    }
+
+    trace!("verify_checksum.{}={}", name, actual);
+    Ok(())
+}
+
+/// Verifies a SHA256 signature of the data, as created with `openssl dgst -sha256 -sign`
+fn verify_signature(data: &[u8], signature: &[u8], key_file: &str) -> Result<(), Box<dyn Error>> {
+    let key = fs::read(key_file)
+        .map_err(|e| format!("Could not read the public key {}: {}", key_file, e))?;
+    let key = PKey::public_key_from_pem(&key)
+        .map_err(|e| format!("Invalid public key {}: {}", key_file, e))?;
+
+    let mut verifier = Verifier::new(MessageDigest::sha256(), &key)?;
+    verifier.update(data)?;
+    // Malformed signatures are treated like invalid ones
+    if !verifier.verify(signature).unwrap_or(false) {
+        return Err(format!("The signature of {} is invalid", CHECKSUMS).into());
+    }
+
+    Ok(())
+}
+
+fn extract(archive: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut archive = ZipArchive::new(Cursor::new(archive))?;
+    let mut file = archive
+        .by_name(BINARY)
+        .map_err(|e| format!("Could not find {} in the download: {}", BINARY, e))?;
+
+    let mut buf = Vec::new();
+    file.read_to_end(&mut buf)?;
+    Ok(buf)
+}
+
+/// Writes the new binary next to the running one, checks that it runs, and renames it
+/// over the old binary. The binary is never left half-written.
+fn replace(exe: &Path, binary: &[u8]) -> Result<(), io::Error> {
+    let tmp = exe.with_file_name(format!(".{}.new-{}", BINARY, std::process::id()));
+
+    let result = (|| -> Result<(), io::Error> {
+        let mut f = File::create(&tmp)?;
+        f.write_all(binary)?;
+        f.sync_all()?;
+        drop(f);
+        fs::set_permissions(&tmp, fs::metadata(exe)?.permissions())?;
+
+        let output = Command::new(&tmp).arg("--version").output()?;
+        if !output.status.success() {
+            return Err(io::Error::other(format!(
+                "the new binary does not run: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+
+        // Windows does not allow replacing a running binary, but it can be renamed
+        if cfg!(windows) {
+            let old = exe.with_extension("old");
+            let _ = fs::remove_file(&old);
+            fs::rename(exe, &old)?;
+            return fs::rename(&tmp, exe).inspect_err(|_| {
+                let _ = fs::rename(&old, exe);
+            });
+        }
+
+        fs::rename(&tmp, exe)
+    })();
+
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verifies_checksums() {
+        let checksums = "\
+            2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824  other.zip\n\
+            2CF24DBA5FB0A30E26E83B2AC5B9E29E1B161E5C1FA7425E73043362938B9824 *saml2aws-auto.zip\n";
+
+        assert!(verify_checksum(b"hello", "saml2aws-auto.zip", checksums).is_ok());
+        assert!(verify_checksum(b"hello!", "saml2aws-auto.zip", checksums).is_err());
+        assert!(verify_checksum(b"hello", "missing.zip", checksums).is_err());
+    }
+
+    fn temp_dir(name: &str) -> std::path::PathBuf {
+        let dir = env::temp_dir().join(format!("saml2aws-auto-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn verifies_signatures() {
+        use openssl::ec::{EcGroup, EcKey};
+        use openssl::nid::Nid;
+        use openssl::sign::Signer;
+
+        let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+        let key = PKey::from_ec_key(EcKey::generate(&group).unwrap()).unwrap();
+
+        let mut signer = Signer::new(MessageDigest::sha256(), &key).unwrap();
+        signer.update(b"checksums").unwrap();
+        let signature = signer.sign_to_vec().unwrap();
+
+        let dir = temp_dir("signature");
+        let key_file = dir.join("release.pub.pem");
+        fs::write(&key_file, key.public_key_to_pem().unwrap()).unwrap();
+        let key_file = key_file.to_str().unwrap();
+
+        assert!(verify_signature(b"checksums", &signature, key_file).is_ok());
+        assert!(verify_signature(b"checksums!", &signature, key_file).is_err());
+        assert!(verify_signature(b"checksums", b"garbage", key_file).is_err());
+        assert!(verify_signature(b"checksums", &signature, "missing.pem").is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn extracts_the_binary() {
+        use zip::write::SimpleFileOptions;
+        use zip::ZipWriter;
+
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        zip.start_file("README.md", SimpleFileOptions::default())
+            .unwrap();
+        zip.write_all(b"readme").unwrap();
+        zip.start_file(BINARY, SimpleFileOptions::default())
+            .unwrap();
+        zip.write_all(b"binary").unwrap();
+        let archive = zip.finish().unwrap().into_inner();
+
+        assert_eq!(extract(&archive).unwrap(), b"binary");
+        assert!(extract(b"not a zip").is_err());
+
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        zip.start_file("README.md", SimpleFileOptions::default())
+            .unwrap();
+        assert!(extract(&zip.finish().unwrap().into_inner()).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn replaces_the_binary_only_if_it_runs() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = temp_dir("replace");
+        let exe = dir.join(BINARY);
+        fs::write(&exe, "#!/bin/sh\necho old\n").unwrap();
+        fs::set_permissions(&exe, fs::Permissions::from_mode(0o755)).unwrap();
+
+        assert!(replace(&exe, b"#!/bin/sh\nexit 1\n").is_err());
+        assert_eq!(fs::read_to_string(&exe).unwrap(), "#!/bin/sh\necho old\n");
+
+        replace(&exe, b"#!/bin/sh\necho new\n").unwrap();
+        assert_eq!(fs::read_to_string(&exe).unwrap(), "#!/bin/sh\necho new\n");
+        assert_eq!(
+            fs::metadata(&exe).unwrap().permissions().mode() & 0o777,
+            0o755
+        );
+
+        // The temporary files are removed in both cases
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
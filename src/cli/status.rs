@@ -0,0 +1,209 @@
+use chrono::prelude::*;
+use chrono::Duration;
+use crossterm::style::Stylize;
+use std::str::FromStr;
+use tabled::settings::object::Columns;
+use tabled::settings::{Alignment, Modify, Style};
+use tabled::{Table, Tabled};
+
+use config;
+use saml2aws_auto::load_credentials_file;
+
+use crate::cli::StatusArgs;
+
+#[derive(Debug, PartialEq)]
+enum SessionState {
+    Valid,
+    Expiring,
+    Expired,
+    NeverRefreshed,
+}
+
+/// Prints a summary of the session health of the selected groups (or all groups if none
+/// are given). Returns false if any of the selected groups needs a refresh.
+pub fn command(cfg: &config::Config, args: &StatusArgs) -> bool {
+    for name in &args.groups {
+        if let Err(e) = cfg.resolve_groups(std::slice::from_ref(name)) {
+            println!(
+                "\nCould not show status for group {}:\n\n\t{}\n",
+                name.as_str().yellow(),
+                e.red()
+            );
+            return false;
+        }
+    }
+
+    let credentials_file = match args.credentials_file {
+        true => match load_credentials_file() {
+            Ok((f, _)) => Some(f),
+            Err(e) => {
+                println!(
+                    "\n{}: {}\n",
+                    "Could not load the AWS credentials file".red(),
+                    e
+                );
+                return false;
+            }
+        },
+        false => None,
+    };
+
+    let threshold = Duration::minutes(args.expiring_within);
+    let now = Local::now();
+
+    #[derive(Debug, Tabled)]
+    struct TableGroupStatus {
+        #[tabled(rename = "Group")]
+        group: String,
+        #[tabled(rename = "Valid")]
+        valid: usize,
+        #[tabled(rename = "Expiring")]
+        expiring: usize,
+        #[tabled(rename = "Expired")]
+        expired: usize,
+        #[tabled(rename = "Never refreshed")]
+        never_refreshed: usize,
+        #[tabled(rename = "Status")]
+        status: String,
+    }
+
+    let mut names: Vec<&String> = cfg
+        .groups
+        .keys()
+        .filter(|name| args.groups.is_empty() || args.groups.contains(name))
+        .collect();
+    names.sort();
+
+    let mut healthy = true;
+    let mut rows: Vec<TableGroupStatus> = vec![];
+
+    for name in names {
+        // Composite groups are made up of the accounts of the groups they include. Accounts
+        // that are part of several of these groups are only counted once.
+        let mut arns: Vec<&String> = vec![];
+        let accounts: Vec<(&String, &config::Group, &config::Account)> = cfg
+            .resolve_groups(std::slice::from_ref(name))
+            .unwrap_or_default()
+            .into_iter()
+            .flat_map(|n| {
+                let (n, group) = cfg.groups.get_key_value(&n).unwrap();
+                group.enabled_accounts().map(move |a| (n, group, a))
+            })
+            .filter(|(_, _, a)| {
+                let duplicate = arns.contains(&&a.arn);
+                arns.push(&a.arn);
+                !duplicate
+            })
+            .collect();
+
+        let states: Vec<SessionState> = accounts
+            .iter()
+            .map(|(name, group, account)| {
+                let valid_until = match credentials_file {
+                    Some(ref f) => f
+                        .section(Some(group.profile_name(name, account)))
+                        .and_then(|s| s.get("expiration"))
+                        .and_then(|e| DateTime::<FixedOffset>::from_str(e).ok()),
+                    None => account.valid_until,
+                };
+
+                // Sessions refresh would renew are expiring as well
+                let threshold = threshold.max(group.refresh_threshold(args.min_remaining));
+
+                session_state(valid_until, now, threshold)
+            })
+            .collect();
+
+        let count = |state: SessionState| states.iter().filter(|&s| *s == state).count();
+
+        let row = TableGroupStatus {
+            group: name.clone(),
+            valid: count(SessionState::Valid),
+            expiring: count(SessionState::Expiring),
+            expired: count(SessionState::Expired),
+            never_refreshed: count(SessionState::NeverRefreshed),
+            status: String::new(),
+        };
+
+        let status = if row.valid == states.len() {
+            "ok".green().to_string()
+        } else {
+            healthy = false;
+            "needs refresh".red().to_string()
+        };
+
+        rows.push(TableGroupStatus { status, ..row });
+    }
+
+    println!(
+        "{}",
+        Table::new(rows)
+            .with(Style::modern())
+            .with(Modify::new(Columns::new(1..5)).with(Alignment::center()))
+    );
+
+    healthy
+}
+
+fn session_state(
+    valid_until: Option<DateTime<FixedOffset>>,
+    now: DateTime<Local>,
+    threshold: Duration,
+) -> SessionState {
+    match valid_until {
+        None => SessionState::NeverRefreshed,
+        Some(valid_until) => {
+            let remaining = valid_until.signed_duration_since(now);
+
+            if remaining <= Duration::zero() {
+                SessionState::Expired
+            } else if remaining <= threshold {
+                SessionState::Expiring
+            } else {
+                SessionState::Valid
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn offset(now: DateTime<Local>, minutes: i64) -> Option<DateTime<FixedOffset>> {
+        Some((now + Duration::minutes(minutes)).fixed_offset())
+    }
+
+    #[test]
+    fn session_state_without_expiration_is_never_refreshed() {
+        let now = Local::now();
+
+        assert_eq!(
+            session_state(None, now, Duration::minutes(15)),
+            SessionState::NeverRefreshed
+        );
+    }
+
+    #[test]
+    fn session_state_respects_threshold() {
+        let now = Local::now();
+        let threshold = Duration::minutes(15);
+
+        assert_eq!(
+            session_state(offset(now, -1), now, threshold),
+            SessionState::Expired
+        );
+        assert_eq!(
+            session_state(offset(now, 10), now, threshold),
+            SessionState::Expiring
+        );
+        assert_eq!(
+            session_state(offset(now, 30), now, threshold),
+            SessionState::Valid
+        );
+        assert_eq!(
+            session_state(offset(now, 30), now, Duration::max_value()),
+            SessionState::Expiring
+        );
+    }
+}
@@ -0,0 +1,226 @@
+use std::env;
+use std::error::Error;
+use std::fs;
+use std::time::Duration as StdDuration;
+
+use chrono::prelude::*;
+use chrono::Duration;
+use reqwest::blocking::ClientBuilder;
+use serde_json;
+
+use config;
+use saml2aws_auto::{http_client_builder, send_with_retries};
+
+extern crate semver;
+
+/// Release queried if no URL is configured
+pub const DEFAULT_URL: &str =
+    "https://api.github.com/repos/Rukenshia/saml2aws-auto/releases/latest";
+
+/// Disables the update check if set to any value
+pub const ENV_NO_UPDATE_CHECK: &str = "SAML2AWS_AUTO_NO_UPDATE_CHECK";
+
+/// How long the result of a check is used before the release is queried again
+const CACHE_HOURS: i64 = 24;
+
+#[derive(Deserialize)]
+pub struct VersionInfo {
+    pub tag_name: String,
+    /// Files attached to the release
+    #[serde(default)]
+    pub assets: Vec<Asset>,
+}
+
+#[derive(Deserialize)]
+pub struct Asset {
+    pub name: String,
+    pub browser_download_url: String,
+}
+
+impl VersionInfo {
+    pub fn asset(&self, name: &str) -> Option<&Asset> {
+        self.assets.iter().find(|a| a.name == name)
+    }
+}
+
+pub enum VersionComparison {
+    HasNewer,
+    IsSame,
+}
+
+/// Result of the last check, kept in the state directory
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedCheck {
+    checked_at: DateTime<FixedOffset>,
+    url: String,
+    /// Tag of the latest release, or none if the check failed (e.g. when offline)
+    latest: Option<String>,
+}
+
+impl CachedCheck {
+    fn is_fresh(&self, url: &str, now: DateTime<FixedOffset>) -> bool {
+        self.url == url
+            && self.checked_at <= now
+            && now - self.checked_at < Duration::hours(CACHE_HOURS)
+    }
+}
+
+/// Returns a client builder for requests to the release API and downloads, which
+/// identify themselves with the version of saml2aws-auto
+pub fn client_builder() -> Result<ClientBuilder, String> {
+    Ok(http_client_builder()?.user_agent(concat!("saml2aws-auto/", env!("CARGO_PKG_VERSION"))))
+}
+
+fn get_latest_version(url: &str) -> Result<VersionInfo, Box<dyn Error>> {
+    Ok(client_builder()?
+        .timeout(StdDuration::from_millis(400))
+        .build()?
+        .get(url)
+        .send()?
+        .error_for_status()?
+        .json()?)
+}
+
+/// Returns the release with the given tag, or the latest release if there is none
+pub fn get_release(url: Option<&str>, tag: Option<&str>) -> Result<VersionInfo, Box<dyn Error>> {
+    let url = release_url(url.unwrap_or(DEFAULT_URL), tag)?;
+
+    Ok(send_with_retries(client_builder()?.build()?.get(&url))?
+        .error_for_status()?
+        .json()?)
+}
+
+/// Derives the URL of a tagged release from the URL of the latest release
+fn release_url(latest: &str, tag: Option<&str>) -> Result<String, String> {
+    match tag {
+        None => Ok(latest.into()),
+        Some(tag) => latest
+            .strip_suffix("/latest")
+            .map(|base| format!("{}/tags/{}", base, tag))
+            .ok_or_else(|| {
+                format!(
+                    "Cannot look up release {} at {}, the URL does not end with /latest",
+                    tag, latest
+                )
+            }),
+    }
+}
+
+/// Returns the name of the release asset for the platform saml2aws-auto was built for,
+/// e.g. `saml2aws-auto-x86_64-unknown-linux-gnu.zip`
+pub fn asset_name() -> String {
+    let os = match env::consts::OS {
+        "linux" => "unknown-linux-gnu",
+        "macos" => "apple-darwin",
+        "windows" => "pc-windows-gnu",
+        other => other,
+    };
+
+    format!("saml2aws-auto-{}-{}.zip", env::consts::ARCH, os)
+}
+
+/// Compares the version with the latest release. The release is queried at most once a
+/// day, also if the query fails.
+pub fn compare_version(to: &str, url: Option<&str>) -> Result<VersionComparison, Box<dyn Error>> {
+    let url = url.unwrap_or(DEFAULT_URL);
+    let cache_path = config::state_directory().map(|dir| dir.join("update-check.json"));
+    let now = Local::now().fixed_offset();
+
+    let cached = cache_path
+        .as_ref()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .and_then(|buf| serde_json::from_str::<CachedCheck>(&buf).ok())
+        .filter(|c| c.is_fresh(url, now));
+
+    let latest = match cached {
+        Some(c) => {
+            trace!("using update check from {}", c.checked_at);
+            c.latest
+        }
+        None => {
+            let latest = match get_latest_version(url) {
+                Ok(info) => Some(info.tag_name),
+                Err(e) => {
+                    debug!("could not check for updates: {}", e);
+                    None
+                }
+            };
+
+            let check = CachedCheck {
+                checked_at: now,
+                url: url.into(),
+                latest: latest.clone(),
+            };
+            match cache_path {
+                Some(ref path) => {
+                    if let Err(e) = path
+                        .parent()
+                        .map_or(Ok(()), fs::create_dir_all)
+                        .and_then(|_| fs::write(path, serde_json::to_vec(&check)?))
+                    {
+                        debug!("could not cache the update check: {}", e);
+                    }
+                }
+                None => debug!("no state directory, the update check is not cached"),
+            }
+
+            latest
+        }
+    };
+
+    let latest = latest.ok_or("The latest release is unknown")?;
+
+    if parse_version(&latest)? > parse_version(to)? {
+        Ok(VersionComparison::HasNewer)
+    } else {
+        Ok(VersionComparison::IsSame)
+    }
+}
+
+/// Parses a version or release tag, which may start with a `v`
+pub fn parse_version(version: &str) -> Result<semver::Version, String> {
+    semver::Version::parse(version.trim().trim_start_matches('v'))
+        .map_err(|e| format!("Invalid version {}: {}", version, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_release_tags() {
+        assert_eq!(
+            parse_version("v1.14.0").unwrap(),
+            semver::Version::new(1, 14, 0)
+        );
+        assert_eq!(
+            parse_version("1.13.4").unwrap(),
+            semver::Version::new(1, 13, 4)
+        );
+        assert!(parse_version("nightly").is_err());
+    }
+
+    #[test]
+    fn derives_urls_of_tagged_releases() {
+        assert_eq!(
+            release_url(DEFAULT_URL, Some("1.14.0")).unwrap(),
+            "https://api.github.com/repos/Rukenshia/saml2aws-auto/releases/tags/1.14.0"
+        );
+        assert_eq!(release_url(DEFAULT_URL, None).unwrap(), DEFAULT_URL);
+        assert!(release_url("https://mirror.example.com/release.json", Some("1.14.0")).is_err());
+    }
+
+    #[test]
+    fn cached_checks_expire() {
+        let now = Local::now().fixed_offset();
+        let check = CachedCheck {
+            checked_at: now - Duration::hours(1),
+            url: DEFAULT_URL.into(),
+            latest: None,
+        };
+
+        assert!(check.is_fresh(DEFAULT_URL, now));
+        assert!(!check.is_fresh("https://mirror.example.com/latest", now));
+        assert!(!check.is_fresh(DEFAULT_URL, now + Duration::hours(CACHE_HOURS)));
+    }
+}
@@ -0,0 +1,103 @@
+use serde_yaml::{Mapping, Value};
+
+/// Version of the config layout written by this release
+pub const CURRENT_VERSION: u64 = 1;
+
+/// Migrations upgrading the config from the version at their index to the next one.
+/// Every change to the layout of the config file needs a new migration and a fixture.
+const MIGRATIONS: &[fn(&mut Mapping)] = &[v0_to_v1];
+
+/// Upgrades the config to the current layout in place. Returns the version the config had
+/// before the upgrade.
+pub fn migrate(value: &mut Value) -> Result<u64, String> {
+    let cfg = value
+        .as_mapping_mut()
+        .ok_or("The config file does not contain a YAML mapping")?;
+
+    let version = match cfg.get("version") {
+        Some(v) => v
+            .as_u64()
+            .ok_or(format!("Invalid config version {:?}", v))?,
+        None => 0,
+    };
+
+    if version > CURRENT_VERSION {
+        return Err(format!(
+            "The config file was written by a newer release of saml2aws-auto (version {}, supported up to {}). Please update saml2aws-auto.",
+            version, CURRENT_VERSION
+        ));
+    }
+
+    for migration in &MIGRATIONS[version as usize..] {
+        migration(cfg);
+    }
+    cfg.insert("version".into(), CURRENT_VERSION.into());
+
+    Ok(version)
+}
+
+/// Releases up to 1.13 did not store a version. Their layout only differs in that a
+/// config without groups may have no (or a null) `groups` key.
+fn v0_to_v1(cfg: &mut Mapping) {
+    if !cfg.get("groups").is_some_and(Value::is_mapping) {
+        cfg.insert("groups".into(), Value::Mapping(Mapping::new()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use config::Config;
+
+    fn load(fixture: &str) -> (u64, Config) {
+        let mut value: Value = serde_yaml::from_str(fixture).unwrap();
+        let version = migrate(&mut value).unwrap();
+
+        (version, serde_yaml::from_value(value).unwrap())
+    }
+
+    #[test]
+    fn migrates_config_from_1_13() {
+        let (version, cfg) = load(include_str!("fixtures/v1.13.yml"));
+
+        assert_eq!(version, 0);
+        assert_eq!(cfg.version, CURRENT_VERSION);
+        assert_eq!(cfg.username.as_deref(), Some("jane.doe"));
+
+        let group = &cfg.groups["payments"];
+        assert_eq!(group.session_duration, Some(3600));
+        assert_eq!(group.accounts.len(), 2);
+        assert!(group.accounts[0].valid_until.is_some());
+        assert!(group.accounts[1].valid_until.is_none());
+    }
+
+    #[test]
+    fn migrates_config_without_groups() {
+        let (_, cfg) = load(include_str!("fixtures/v1.13-empty.yml"));
+
+        assert!(cfg.groups.is_empty());
+    }
+
+    #[test]
+    fn keeps_current_config() {
+        let (version, cfg) = load(include_str!("fixtures/v1.yml"));
+
+        assert_eq!(version, CURRENT_VERSION);
+        assert_eq!(cfg.idps["partner"].username.as_deref(), Some("jane"));
+        assert_eq!(cfg.groups["all-prod"].includes, vec!["payments"]);
+        assert_eq!(cfg.groups["payments"].selectors.len(), 1);
+        assert_eq!(cfg.groups["payments"].accounts.len(), 2);
+        assert!(cfg.groups["payments"].accounts[1].disabled);
+
+        // Sessions are kept in the state file, but still read from older files
+        assert!(cfg.groups["payments"].accounts[0].valid_until.is_some());
+    }
+
+    #[test]
+    fn rejects_newer_config() {
+        let mut value: Value =
+            serde_yaml::from_str(&format!("version: {}", CURRENT_VERSION + 1)).unwrap();
+
+        assert!(migrate(&mut value).is_err());
+    }
+}
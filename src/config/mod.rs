@@ -0,0 +1,1415 @@
+//! The config file, its groups, accounts and IdPs, and the passwords stored in the keyring
+
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+use std::fmt;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+
+use chrono::prelude::*;
+use chrono::Duration;
+
+use dirs;
+use keyring::Entry;
+use regex::Regex;
+use serde_yaml;
+
+use roles::RoleInfo;
+use saml::arn_to_role_info;
+use secret::Secret;
+
+mod migrations;
+mod password;
+mod state;
+
+use self::migrations::{migrate, CURRENT_VERSION};
+pub use self::password::PasswordSource;
+pub use self::state::state_directory;
+use self::state::state_filename;
+
+/// The config file, with the IdPs to log in with and the groups of accounts to refresh
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Config {
+    /// Version of the layout of the config file, see the migrations module
+    #[serde(default)]
+    pub version: u64,
+    #[serde(default = "default_filename")]
+    filename: String,
+    #[serde(skip)]
+    state_filename: String,
+    /// Backup of the file written when it was upgraded to the current version on load
+    #[serde(skip)]
+    pub upgrade_backup: Option<String>,
+    pub idp_url: String,
+    pub username: Option<String>,
+    pub mfa_device: Option<String>,
+    /// Where the password is read from. Defaults to the keyring.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password_source: Option<PasswordSource>,
+
+    #[serde(skip_serializing)]
+    pub password: Option<Secret>,
+
+    /// Additional identity providers, by name. The IdP configured above is the default
+    /// one used by groups that do not reference a named IdP.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub idps: BTreeMap<String, Idp>,
+
+    /// File the log of every run is written to, see `--log-file`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub log_file: Option<String>,
+
+    /// Proxies used for all requests. If set, the proxy environment variables are ignored.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proxy: Option<ProxyConfig>,
+
+    /// Certificates used for all requests
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tls: Option<TlsConfig>,
+
+    /// Timeouts and retries of all requests
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub http: Option<HttpConfig>,
+
+    /// Checking for new releases on startup
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub update_check: Option<UpdateCheckConfig>,
+
+    pub groups: HashMap<String, Group>,
+}
+
+/// Proxy settings, equivalent to the `http_proxy`, `https_proxy`, `all_proxy` and
+/// `no_proxy` environment variables
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProxyConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub http: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub https: Option<String>,
+    /// Used for requests without a proxy for their scheme, e.g. `socks5h://localhost:1080`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub all: Option<String>,
+    /// Hosts, domains (e.g. `.corp.example.com`) and networks that are connected to directly
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub no_proxy: Vec<String>,
+}
+
+/// Timeouts and retries of requests to the IdP, STS and GitHub
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HttpConfig {
+    /// Seconds to wait for a connection. Defaults to 10.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub connect_timeout: Option<u64>,
+    /// Seconds to wait for a complete response. Defaults to 30.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<u64>,
+    /// How often failed requests are retried, e.g. after a connection reset, a server
+    /// error or STS throttling. Defaults to 3.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retries: Option<u32>,
+}
+
+/// Settings of the check for new releases
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UpdateCheckConfig {
+    /// Defaults to true
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub enabled: Option<bool>,
+    /// URL of the latest release in the format of the GitHub API, e.g. of an internal mirror
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// PEM file with the public key the checksums of releases are signed with. If set,
+    /// self-update only installs releases with a valid signature.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub public_key: Option<String>,
+}
+
+/// TLS settings, e.g. for IdPs behind a corporate CA or requiring client certificates
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TlsConfig {
+    /// PEM file with CA certificates that are trusted in addition to the ones of the system
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ca_bundle: Option<String>,
+    /// PEM file with the client certificate. It may also contain the key.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_cert: Option<String>,
+    /// PEM file with the private key of the client certificate
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_key: Option<String>,
+    /// PKCS#12 file with the client certificate and key, instead of PEM files
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_pkcs12: Option<String>,
+    /// Where the password of the PKCS#12 file is read from, e.g. `env:CLIENT_CERT_PASSWORD`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_pkcs12_password: Option<PasswordSource>,
+    /// Accepts invalid certificates. Only meant for local test IdPs.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub insecure: bool,
+}
+
+/// A Keycloak instance the user logs in to
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Idp {
+    pub url: String,
+    pub username: Option<String>,
+    pub mfa_device: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password_source: Option<PasswordSource>,
+
+    #[serde(skip)]
+    pub password: Option<Secret>,
+}
+
+/// Accounts that are refreshed together, with settings shared by all of them
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub struct Group {
+    pub session_duration: Option<i64>,
+    pub sts_endpoint: Option<String>,
+    /// Minimum remaining session lifetime in seconds. Accounts with less remaining
+    /// lifetime are refreshed even if their session is still valid.
+    pub min_remaining: Option<i64>,
+    /// AWS region written to the profiles of the group. Also selects the regional STS
+    /// endpoint if no endpoint is configured.
+    pub region: Option<String>,
+    /// Template for the profile names of the accounts, e.g. `{group}-{account}`.
+    /// Defaults to the account name.
+    pub profile_template: Option<String>,
+    /// Name of the IdP used to log in. Defaults to the IdP at the top of the config.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub idp: Option<String>,
+    /// Where the credentials of the accounts are written to
+    #[serde(default, skip_serializing_if = "Storage::is_plaintext")]
+    pub storage: Storage,
+    /// Rules used to synchronise the accounts of the group with the IdP on refresh.
+    /// Groups without selectors are static.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub selectors: Vec<AccountSelector>,
+    /// Names of other groups whose accounts are part of this group. Accounts are only
+    /// stored in the included groups, so their sessions are shared.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub includes: Vec<String>,
+    #[serde(default)]
+    pub accounts: Vec<Account>,
+}
+
+/// Where the credentials of a group are stored
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize, clap::ValueEnum,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Storage {
+    /// The AWS credentials file
+    #[default]
+    Plaintext,
+    /// The encrypted credential cache, see the exec and credential-process commands
+    Encrypted,
+}
+
+impl Storage {
+    fn is_plaintext(&self) -> bool {
+        *self == Storage::Plaintext
+    }
+}
+
+/// A role of an AWS account, written to its own profile
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub struct Account {
+    pub name: String,
+    pub arn: String,
+    /// Expiration of the session. Stored in the state file, older config files contain
+    /// it as well.
+    #[serde(default, skip_serializing)]
+    pub valid_until: Option<DateTime<FixedOffset>>,
+
+    /// Overrides the session duration of the group
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_duration: Option<i64>,
+    /// Overrides the STS endpoint of the group
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sts_endpoint: Option<String>,
+    /// Overrides the region of the group
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub region: Option<String>,
+    /// Overrides the profile name generated from the profile template of the group
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub profile: Option<String>,
+    /// Disabled accounts are skipped on refresh
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub disabled: bool,
+}
+
+/// Environment variables overriding the settings of the default IdP
+pub const ENV_IDP_URL: &str = "SAML2AWS_AUTO_IDP_URL";
+const ENV_USERNAME: &str = "SAML2AWS_AUTO_USERNAME";
+const ENV_MFA_DEVICE: &str = "SAML2AWS_AUTO_MFA_DEVICE";
+
+/// Returns the path of the config file in the XDG config directory, i.e.
+/// `$XDG_CONFIG_HOME/saml2aws-auto/config.yml` or `~/.config/saml2aws-auto/config.yml`
+pub fn default_filename() -> String {
+    xdg_filename(env::var_os("XDG_CONFIG_HOME"), &dirs::home_dir().unwrap())
+}
+
+fn xdg_filename(config_home: Option<std::ffi::OsString>, home: &Path) -> String {
+    let mut path = match config_home {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => home.join(".config"),
+    };
+    path.push("saml2aws-auto");
+    path.push("config.yml");
+
+    path.to_string_lossy().into_owned()
+}
+
+/// Returns the log file configured in the config file. Logging is set up before the
+/// config is loaded, so only this setting is read here.
+pub fn log_file(path: &str) -> Option<String> {
+    read_setting(path, "log_file")
+}
+
+/// Returns the proxy settings of the config file. Like the log file, they are needed
+/// before the config is loaded.
+pub fn proxy(path: &str) -> Option<ProxyConfig> {
+    read_setting(path, "proxy")
+}
+
+/// Returns the TLS settings of the config file, see `proxy`
+pub fn tls(path: &str) -> Option<TlsConfig> {
+    read_setting(path, "tls")
+}
+
+/// Returns the timeouts and retries of the config file, see `proxy`
+pub fn http(path: &str) -> Option<HttpConfig> {
+    read_setting(path, "http")
+}
+
+/// Returns the settings of the update check, which runs before the config is loaded
+pub fn update_check(path: &str) -> Option<UpdateCheckConfig> {
+    read_setting(path, "update_check")
+}
+
+fn read_setting<T: serde::de::DeserializeOwned>(path: &str, key: &str) -> Option<T> {
+    let buf = fs::read_to_string(path).ok()?;
+    let value: serde_yaml::Value = serde_yaml::from_str(&buf).ok()?;
+
+    serde_yaml::from_value(value.get(key)?.clone()).ok()
+}
+
+/// Loads the config file and its session state, upgrading older layouts. Returns an empty
+/// config if the file does not exist yet.
+pub fn load_or_default(path: &str) -> Result<Config, io::Error> {
+    if Path::new(path).exists() {
+        let mut f = File::open(path)?;
+
+        let mut buf = String::new();
+
+        f.read_to_string(&mut buf)?;
+
+        let mut value: serde_yaml::Value = serde_yaml::from_str(&buf).map_err(io::Error::other)?;
+        let version = migrate(&mut value).map_err(io::Error::other)?;
+
+        match serde_yaml::from_value::<Config>(value) {
+            Ok(mut cfg) => {
+                cfg.filename = path.to_owned();
+                cfg.state_filename = state_filename(&cfg.directory());
+                cfg.load_state()?;
+
+                // Keep the file in the layout of the release that wrote it, in case the
+                // upgrade needs to be rolled back
+                if version < CURRENT_VERSION {
+                    let backup = format!("{}.v{}.bak", path, version);
+                    fs::write(&backup, &buf)?;
+                    cfg.save()?;
+
+                    info!("upgraded the config file from version {}", version);
+                    cfg.upgrade_backup = Some(backup);
+                }
+
+                // Other password sources are only read when the password is needed. The
+                // username may be overridden by the environment.
+                if let Some(ref username) = cfg.default_username() {
+                    if uses_keyring(&cfg.password_source) {
+                        cfg.password = match get_password(None, username) {
+                            Ok(p) => Some(p),
+                            Err(_) => None,
+                        };
+                    }
+                }
+
+                for (name, idp) in cfg.idps.iter_mut() {
+                    if let Some(ref username) = idp.username {
+                        if uses_keyring(&idp.password_source) {
+                            idp.password = get_password(Some(name), username).ok();
+                        }
+                    }
+                }
+
+                Ok(cfg)
+            }
+            Err(e) => Err(io::Error::new(io::ErrorKind::Other, e)),
+        }
+    } else {
+        Ok(Config::default(path))
+    }
+}
+
+/// Writes the contents to a temporary file next to the path and renames it, which
+/// replaces the file atomically. A symlinked file is replaced at its target, and keeps
+/// its permissions.
+pub fn write_atomic(path: &str, contents: &[u8]) -> Result<(), io::Error> {
+    let path = fs::canonicalize(path).unwrap_or_else(|_| PathBuf::from(path));
+    let permissions = fs::metadata(&path).map(|m| m.permissions()).ok();
+
+    let mut tmp = path.clone().into_os_string();
+    tmp.push(format!(".tmp-{}", std::process::id()));
+    let tmp = PathBuf::from(tmp);
+
+    let result = File::create(&tmp).and_then(|mut f| {
+        if let Some(permissions) = permissions {
+            f.set_permissions(permissions)?;
+        }
+        f.write_all(contents)?;
+        f.sync_all()
+    });
+
+    match result.and_then(|_| fs::rename(&tmp, &path)) {
+        Ok(_) => Ok(()),
+        Err(e) => {
+            let _ = fs::remove_file(&tmp);
+            Err(e)
+        }
+    }
+}
+
+/// Returns true if the password of the source is stored in the keyring
+pub fn uses_keyring(source: &Option<PasswordSource>) -> bool {
+    matches!(source, None | Some(PasswordSource::Keyring))
+}
+
+/// Returns the name of the keyring service the passwords of the IdP are stored in. The
+/// default IdP keeps the service name used before named IdPs were supported.
+fn keyring_service(idp: Option<&str>) -> String {
+    match idp {
+        Some(name) => format!("saml2aws-auto/{}", name),
+        None => "saml2aws-auto".into(),
+    }
+}
+
+/// Returns the password of the user stored in the keyring for the IdP
+pub fn get_password(idp: Option<&str>, username: &str) -> Result<Secret, keyring::Error> {
+    Entry::new(&keyring_service(idp), username)?
+        .get_password()
+        .map(Secret::from)
+}
+
+/// Stores the password of the user in the keyring for the IdP
+pub fn set_password(
+    idp: Option<&str>,
+    username: &str,
+    password: &Secret,
+) -> Result<(), keyring::Error> {
+    Entry::new(&keyring_service(idp), username)?.set_password(password.expose())
+}
+
+impl Config {
+    /// Returns an empty config that is saved to `filename`
+    pub fn default(filename: &str) -> Self {
+        let mut cfg = Config {
+            version: CURRENT_VERSION,
+            filename: filename.to_owned(),
+            state_filename: String::new(),
+            upgrade_backup: None,
+            idp_url: "localhost".into(),
+            username: None,
+            password: None,
+            groups: HashMap::new(),
+            mfa_device: None,
+            password_source: None,
+            idps: BTreeMap::new(),
+            log_file: None,
+            proxy: None,
+            tls: None,
+            http: None,
+            update_check: None,
+        };
+
+        cfg.state_filename = state_filename(&cfg.directory());
+        cfg
+    }
+
+    /// Returns the IdP with the given name, or the default IdP if no name is given. The
+    /// settings of the default IdP can be overridden with environment variables, which
+    /// are never written to the config file.
+    pub fn idp(&self, name: Option<&str>) -> Option<Idp> {
+        self.idp_with(name, |k| env::var(k).ok())
+    }
+
+    /// Returns the IdP like `idp`, reading the overrides with `var`
+    fn idp_with<F: Fn(&str) -> Option<String>>(&self, name: Option<&str>, var: F) -> Option<Idp> {
+        match name {
+            Some(name) => self.idps.get(name).cloned(),
+            None => Some(Idp {
+                url: var(ENV_IDP_URL).unwrap_or_else(|| self.idp_url.clone()),
+                username: self.default_username_with(&var),
+                mfa_device: var(ENV_MFA_DEVICE).or_else(|| self.mfa_device.clone()),
+                password_source: self.password_source.clone(),
+                password: self.password.clone(),
+            }),
+        }
+    }
+
+    /// Returns the username of the default IdP
+    fn default_username(&self) -> Option<String> {
+        self.default_username_with(|k| env::var(k).ok())
+    }
+
+    fn default_username_with<F: Fn(&str) -> Option<String>>(&self, var: F) -> Option<String> {
+        var(ENV_USERNAME).or_else(|| self.username.clone())
+    }
+
+    /// Returns the directory the config file is stored in
+    pub fn directory(&self) -> PathBuf {
+        match Path::new(&self.filename).parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+            _ => PathBuf::from("."),
+        }
+    }
+
+    /// Writes the config and the session state. Both files are replaced atomically, so
+    /// they are never left half-written.
+    pub fn save(&self) -> Result<(), io::Error> {
+        let buf = serde_yaml::to_string(self).map_err(io::Error::other)?;
+
+        write_atomic(&self.filename, buf.as_bytes())?;
+        self.save_state()
+    }
+
+    /// Expands composite groups into the groups they include, recursively. The result
+    /// contains every group at most once, and the composite groups themselves since they
+    /// can have accounts of their own.
+    pub fn resolve_groups(&self, names: &[String]) -> Result<Vec<String>, String> {
+        let mut resolved: Vec<String> = vec![];
+        let mut pending: Vec<(String, Option<&String>)> =
+            names.iter().rev().map(|n| (n.clone(), None)).collect();
+
+        while let Some((name, included_by)) = pending.pop() {
+            if resolved.contains(&name) {
+                continue;
+            }
+
+            let (key, group) = match (self.groups.get_key_value(&name), included_by) {
+                (Some(entry), _) => entry,
+                (None, Some(parent)) => {
+                    return Err(format!(
+                        "Group {} included by {} does not exist",
+                        name, parent
+                    ))
+                }
+                (None, None) => return Err(format!("Group {} does not exist", name)),
+            };
+
+            pending.extend(group.includes.iter().rev().map(|n| (n.clone(), Some(key))));
+            resolved.push(name);
+        }
+
+        Ok(resolved)
+    }
+
+    /// Checks that the IdP of a group is configured, its selectors are valid and the groups
+    /// it includes exist without including the group again.
+    pub fn check_group(&self, name: &str) -> Result<(), String> {
+        let group = match self.groups.get(name) {
+            Some(g) => g,
+            None => return Err(format!("Group {} does not exist", name)),
+        };
+
+        if let Some(ref idp) = group.idp {
+            if !self.idps.contains_key(idp) {
+                return Err(format!("The IdP {} of group {} does not exist", idp, name));
+            }
+        }
+
+        for selector in &group.selectors {
+            selector
+                .validate()
+                .map_err(|e| format!("Invalid selector of group {}: {}", name, e))?;
+        }
+
+        if self
+            .resolve_groups(&group.includes)?
+            .iter()
+            .any(|n| n == name)
+        {
+            return Err(format!("Group {} includes itself", name));
+        }
+
+        Ok(())
+    }
+
+    /// Returns the groups by the IdP they log in with, keeping the order of the groups.
+    /// Groups that only include other groups need no login and are left out.
+    pub fn groups_by_idp(&self, names: &[String]) -> Vec<(Option<String>, Vec<String>)> {
+        let mut by_idp: Vec<(Option<String>, Vec<String>)> = vec![];
+
+        for name in names {
+            let group = match self.groups.get(name) {
+                Some(g) => g,
+                None => continue,
+            };
+
+            if !group.includes.is_empty() && group.accounts.is_empty() && group.selectors.is_empty()
+            {
+                continue;
+            }
+
+            match by_idp.iter_mut().find(|(idp, _)| idp == &group.idp) {
+                Some((_, groups)) => groups.push(name.clone()),
+                None => by_idp.push((group.idp.clone(), vec![name.clone()])),
+            }
+        }
+
+        by_idp
+    }
+}
+
+impl Idp {
+    /// Returns the password of the user, read from the password source of the IdP
+    pub fn resolve_password(&self) -> Result<Secret, String> {
+        match self.password_source {
+            None | Some(PasswordSource::Keyring) => self.password.clone().ok_or_else(|| {
+                "Password could not be found, please run saml2aws-auto configure or provide a password by supplying the --password flag".into()
+            }),
+            Some(ref source) => source.read(),
+        }
+    }
+}
+
+impl Group {
+    /// Returns the minimum remaining session lifetime of the accounts. The override, e.g.
+    /// from the command line, takes precedence over the setting of the group. Values too
+    /// large to represent make every session need a refresh.
+    pub fn refresh_threshold(&self, min_remaining: Option<i64>) -> Duration {
+        let seconds = min_remaining.or(self.min_remaining).unwrap_or(0);
+        Duration::try_seconds(seconds).unwrap_or(Duration::max_value())
+    }
+
+    /// Returns the STS endpoint to use for the account. Endpoints take precedence over
+    /// regional endpoints, and settings of the account over the ones of the group.
+    pub fn sts_endpoint_for(&self, account: &Account) -> Option<String> {
+        account
+            .sts_endpoint
+            .clone()
+            .or_else(|| account.region.as_ref().map(|r| regional_sts_endpoint(r)))
+            .or_else(|| self.sts_endpoint.clone())
+            .or_else(|| self.region.as_ref().map(|r| regional_sts_endpoint(r)))
+    }
+
+    /// Returns the session duration to request for the account
+    pub fn session_duration_for(&self, account: &Account) -> Option<i64> {
+        account.session_duration.or(self.session_duration)
+    }
+
+    /// Returns the region written to the profile of the account
+    pub fn region_for<'a>(&'a self, account: &'a Account) -> Option<&'a str> {
+        account.region.as_deref().or(self.region.as_deref())
+    }
+
+    /// Returns the accounts that are refreshed, leaving out disabled ones
+    pub fn enabled_accounts(&self) -> impl Iterator<Item = &Account> {
+        self.accounts.iter().filter(|a| !a.disabled)
+    }
+
+    /// Returns the name of the AWS profile the credentials of the account are written to.
+    /// A profile set on the account takes precedence. Otherwise, the profile template
+    /// supports the placeholders `{group}`, `{account}`, `{account_id}` and `{role}`.
+    pub fn profile_name(&self, group_name: &str, account: &Account) -> String {
+        if let Some(ref profile) = account.profile {
+            return profile.clone();
+        }
+
+        let template = match self.profile_template {
+            Some(ref t) => t,
+            None => return account.name.clone(),
+        };
+
+        // Accounts with a malformed ARN cannot be refreshed, but may still be listed
+        let (account_id, role_name) = match arn_to_role_info(&account.arn) {
+            Some(info) => info,
+            None => return account.name.clone(),
+        };
+
+        template
+            .replace("{group}", group_name)
+            .replace("{account}", &account.name)
+            .replace("{account_id}", &account_id)
+            .replace("{role}", &role_name)
+    }
+
+    /// Returns the profile name and region the credentials of each account are written
+    /// to, by account name
+    pub fn profiles(&self, group_name: &str) -> HashMap<String, (String, Option<String>)> {
+        self.accounts
+            .iter()
+            .map(|a| {
+                let profile = self.profile_name(group_name, a);
+                let region = self.region_for(a).map(String::from);
+                (a.name.clone(), (profile, region))
+            })
+            .collect()
+    }
+
+    /// Clears the session of the accounts whose profile or region changed since `before`
+    /// was taken with [`Group::profiles`], so the next refresh writes them again
+    pub fn expire_moved_profiles(
+        &mut self,
+        group_name: &str,
+        before: &HashMap<String, (String, Option<String>)>,
+    ) {
+        let after = self.profiles(group_name);
+
+        for account in &mut self.accounts {
+            if before.get(&account.name) != after.get(&account.name) {
+                account.valid_until = None;
+            }
+        }
+    }
+
+    /// Updates the accounts of the group to the ones matched by its selectors. Accounts
+    /// that still match keep their session. Returns the names of the added and removed
+    /// accounts.
+    ///
+    /// The group is left unchanged if the account names are not known, because the AWS
+    /// sign-in page did not list them, or if no account is selected at all.
+    pub fn reconcile(&mut self, roles: &[RoleInfo]) -> Result<(Vec<String>, Vec<String>), String> {
+        if self.selectors.is_empty() {
+            return Ok((vec![], vec![]));
+        }
+
+        if roles.iter().any(|r| r.account_name == r.account_id) {
+            return Err("the account names could not be read from the AWS sign-in page".into());
+        }
+
+        let mut selected: Vec<Account> = vec![];
+        for selector in &self.selectors {
+            for account in selector.select(roles).map_err(|e| e.to_string())? {
+                if !selected.iter().any(|a| a.name == account.name) {
+                    selected.push(account);
+                }
+            }
+        }
+
+        if selected.is_empty() {
+            return Err("no account matches the selectors, the accounts are kept".into());
+        }
+
+        let removed: Vec<String> = self
+            .accounts
+            .iter()
+            .filter(|a| !selected.iter().any(|s| s.name == a.name))
+            .map(|a| a.name.clone())
+            .collect();
+        self.accounts.retain(|a| !removed.contains(&a.name));
+
+        let mut added: Vec<String> = vec![];
+        for account in selected {
+            match self.accounts.iter_mut().find(|a| a.name == account.name) {
+                Some(existing) => {
+                    if existing.arn != account.arn {
+                        existing.arn = account.arn;
+                        existing.valid_until = None;
+                    }
+                }
+                None => {
+                    added.push(account.name.clone());
+                    self.accounts.push(account);
+                }
+            }
+        }
+
+        Ok((added, removed))
+    }
+}
+
+impl Account {
+    /// Returns true if the session of this account is valid for at least `min_remaining`.
+    pub fn session_valid(&self, min_remaining: Duration) -> bool {
+        let valid_until = match self.valid_until {
+            Some(valid_until) => valid_until,
+            None => return false,
+        };
+
+        // A threshold beyond the range of dates can never be met
+        match Local::now().checked_add_signed(min_remaining) {
+            Some(threshold) => threshold < valid_until.with_timezone::<Local>(&Local),
+            None => false,
+        }
+    }
+}
+
+/// Parses an account given as `NAME=ARN`, where the ARN has to be an IAM role ARN.
+pub fn parse_account(s: &str) -> Result<Account, String> {
+    let (name, arn) = match s.split_once('=') {
+        Some((name, arn)) if !name.trim().is_empty() => (name.trim(), arn.trim()),
+        _ => return Err(format!("invalid account '{}', expected NAME=ARN", s)),
+    };
+
+    let parts: Vec<&str> = arn.split(':').collect();
+    if parts.len() != 6
+        || parts[0] != "arn"
+        || parts[2] != "iam"
+        || parts[4].is_empty()
+        || !parts[5].starts_with("role/")
+    {
+        return Err(format!(
+            "invalid role ARN '{}', expected a value like arn:aws:iam::123456789012:role/RoleName",
+            arn
+        ));
+    }
+
+    Ok(Account {
+        name: name.into(),
+        arn: arn.into(),
+        ..Default::default()
+    })
+}
+
+/// Longest duration accepted by `parse_duration`, the maximum session duration of STS
+const MAX_DURATION: i64 = 12 * 3600;
+
+/// Parses a duration such as `90s`, `15m` or `1h` into seconds. A number without
+/// a unit is interpreted as seconds.
+pub fn parse_duration(s: &str) -> Result<i64, String> {
+    let s = s.trim();
+    let (value, multiplier) = match s.chars().last() {
+        Some('s') => (&s[..s.len() - 1], 1),
+        Some('m') => (&s[..s.len() - 1], 60),
+        Some('h') => (&s[..s.len() - 1], 3600),
+        _ => (s, 1),
+    };
+
+    match value.parse::<i64>().map(|v| (v, v.checked_mul(multiplier))) {
+        Ok((v, Some(seconds))) if v >= 0 && seconds <= MAX_DURATION => Ok(seconds),
+        Ok((v, _)) if v >= 0 => Err(format!(
+            "duration '{}' is longer than the maximum session duration of 12h",
+            s
+        )),
+        _ => Err(format!(
+            "invalid duration '{}', expected a value like 90s, 15m or 1h",
+            s
+        )),
+    }
+}
+
+/// AccountSelector describes which accounts and roles of the IdP belong to a group.
+///
+/// Accounts listed explicitly by name or id are combined, while the prefix and the
+/// regular expressions further narrow down the selection. If an account matches
+/// several roles, the role listed first in `roles` wins, followed by roles matching
+/// `role_match`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub struct AccountSelector {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prefix: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub accounts: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub account_ids: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub matches: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exclude: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub roles: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub role_match: Option<String>,
+}
+
+impl AccountSelector {
+    pub fn has_account_filters(&self) -> bool {
+        self.prefix.is_some()
+            || !self.accounts.is_empty()
+            || !self.account_ids.is_empty()
+            || self.matches.is_some()
+    }
+
+    pub fn has_role_filters(&self) -> bool {
+        !self.roles.is_empty() || self.role_match.is_some()
+    }
+
+    /// Checks that all regular expressions of the selector compile
+    pub fn validate(&self) -> Result<(), regex::Error> {
+        compile(&self.matches)?;
+        compile(&self.exclude)?;
+        compile(&self.role_match)?;
+        Ok(())
+    }
+
+    /// Returns an account for every AWS account matching the selector, using the
+    /// preferred role of each account
+    pub fn select(&self, roles: &[RoleInfo]) -> Result<Vec<Account>, regex::Error> {
+        let matches = compile(&self.matches)?;
+        let exclude = compile(&self.exclude)?;
+        let role_match = compile(&self.role_match)?;
+
+        let mut selected: Vec<(usize, &RoleInfo)> = vec![];
+
+        for role in roles {
+            let listed = (self.accounts.is_empty() && self.account_ids.is_empty())
+                || self.accounts.contains(&role.account_name)
+                || self.account_ids.contains(&role.account_id);
+
+            if !listed
+                || !self
+                    .prefix
+                    .as_ref()
+                    .is_none_or(|p| role.account_name.starts_with(p))
+                || !matches
+                    .as_ref()
+                    .is_none_or(|re| re.is_match(&role.account_name))
+                || exclude
+                    .as_ref()
+                    .is_some_and(|re| re.is_match(&role.account_name))
+            {
+                continue;
+            }
+
+            let rank = match self.roles.iter().position(|r| r == &role.role_name) {
+                Some(rank) => rank,
+                None if role_match
+                    .as_ref()
+                    .is_some_and(|re| re.is_match(&role.role_name)) =>
+                {
+                    self.roles.len()
+                }
+                None => continue,
+            };
+
+            match selected
+                .iter_mut()
+                .find(|(_, r)| r.account_id == role.account_id)
+            {
+                Some(existing) => {
+                    if rank < existing.0 {
+                        *existing = (rank, role);
+                    }
+                }
+                None => selected.push((rank, role)),
+            }
+        }
+
+        Ok(selected
+            .into_iter()
+            .map(|(_, r)| Account {
+                name: r.account_name.clone(),
+                arn: r.arn.clone(),
+                ..Default::default()
+            })
+            .collect())
+    }
+}
+
+impl fmt::Display for AccountSelector {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut parts: Vec<String> = vec![];
+
+        if let Some(prefix) = &self.prefix {
+            parts.push(format!("prefix={}", prefix));
+        }
+        if !self.accounts.is_empty() {
+            parts.push(format!("accounts={}", self.accounts.join(",")));
+        }
+        if !self.account_ids.is_empty() {
+            parts.push(format!("account-ids={}", self.account_ids.join(",")));
+        }
+        if let Some(matches) = &self.matches {
+            parts.push(format!("match={}", matches));
+        }
+        if let Some(exclude) = &self.exclude {
+            parts.push(format!("exclude={}", exclude));
+        }
+        if !self.roles.is_empty() {
+            parts.push(format!("roles={}", self.roles.join(",")));
+        }
+        if let Some(role_match) = &self.role_match {
+            parts.push(format!("role-match={}", role_match));
+        }
+
+        write!(f, "{}", parts.join(" "))
+    }
+}
+
+fn regional_sts_endpoint(region: &str) -> String {
+    format!("https://sts.{}.amazonaws.com/", region)
+}
+
+fn is_false(value: &bool) -> bool {
+    !value
+}
+
+fn compile(pattern: &Option<String>) -> Result<Option<Regex>, regex::Error> {
+    pattern.as_ref().map(|p| Regex::new(p)).transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_duration_supports_units() {
+        assert_eq!(parse_duration("90"), Ok(90));
+        assert_eq!(parse_duration("90s"), Ok(90));
+        assert_eq!(parse_duration("15m"), Ok(900));
+        assert_eq!(parse_duration("2h"), Ok(7200));
+    }
+
+    #[test]
+    fn parse_duration_rejects_invalid_values() {
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("m").is_err());
+        assert!(parse_duration("-5m").is_err());
+        assert!(parse_duration("9999999999999999h").is_err());
+        assert!(parse_duration("1000000000000h").is_err());
+        assert!(parse_duration("13h").is_err());
+        assert_eq!(parse_duration("12h"), Ok(43200));
+        assert!(parse_duration("5d").is_err());
+    }
+
+    #[test]
+    fn session_valid_respects_min_remaining() {
+        let account = Account {
+            name: "test".into(),
+            arn: "arn:aws:iam::123456789012:role/ARoleName".into(),
+            valid_until: Some((Local::now() + Duration::minutes(10)).fixed_offset()),
+            ..Default::default()
+        };
+
+        assert!(account.session_valid(Duration::zero()));
+        assert!(account.session_valid(Duration::minutes(5)));
+        assert!(!account.session_valid(Duration::minutes(15)));
+    }
+
+    #[test]
+    fn huge_min_remaining_needs_a_refresh() {
+        let account = Account {
+            name: "test".into(),
+            arn: "arn:aws:iam::123456789012:role/ARoleName".into(),
+            valid_until: Some((Local::now() + Duration::minutes(10)).fixed_offset()),
+            ..Default::default()
+        };
+        let group = Group {
+            min_remaining: Some(i64::MAX),
+            ..Default::default()
+        };
+
+        assert!(!account.session_valid(group.refresh_threshold(None)));
+        assert!(!account.session_valid(Duration::max_value()));
+        assert!(account.session_valid(group.refresh_threshold(Some(60))));
+    }
+
+    fn role(account_name: &str, account_id: &str, role_name: &str) -> RoleInfo {
+        RoleInfo {
+            account_name: account_name.into(),
+            account_id: account_id.into(),
+            role_name: role_name.into(),
+            arn: format!("arn:aws:iam::{}:role/{}", account_id, role_name),
+        }
+    }
+
+    fn roles() -> Vec<RoleInfo> {
+        vec![
+            role("payments-prod-eu", "1", "Administrator"),
+            role("payments-prod-eu", "1", "ReadOnly"),
+            role("payments-dev-eu", "2", "Administrator"),
+            role("search-prod-us", "3", "ReadOnly"),
+        ]
+    }
+
+    fn names(accounts: Vec<Account>) -> Vec<String> {
+        accounts.into_iter().map(|a| a.name).collect()
+    }
+
+    #[test]
+    fn select_by_prefix_and_role() {
+        let selector = AccountSelector {
+            prefix: Some("payments-".into()),
+            roles: vec!["Administrator".into()],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            names(selector.select(&roles()).unwrap()),
+            vec!["payments-prod-eu", "payments-dev-eu"]
+        );
+    }
+
+    #[test]
+    fn select_by_regex_with_exclude() {
+        let selector = AccountSelector {
+            matches: Some("-prod-".into()),
+            exclude: Some("^search-".into()),
+            role_match: Some(".*".into()),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            names(selector.select(&roles()).unwrap()),
+            vec!["payments-prod-eu"]
+        );
+    }
+
+    #[test]
+    fn select_by_account_id_and_name() {
+        let selector = AccountSelector {
+            accounts: vec!["search-prod-us".into()],
+            account_ids: vec!["2".into()],
+            roles: vec!["Administrator".into(), "ReadOnly".into()],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            names(selector.select(&roles()).unwrap()),
+            vec!["payments-dev-eu", "search-prod-us"]
+        );
+    }
+
+    #[test]
+    fn select_prefers_roles_in_given_order() {
+        let selector = AccountSelector {
+            account_ids: vec!["1".into()],
+            roles: vec!["ReadOnly".into(), "Administrator".into()],
+            ..Default::default()
+        };
+
+        let accounts = selector.select(&roles()).unwrap();
+
+        assert_eq!(accounts.len(), 1);
+        assert_eq!(accounts[0].arn, "arn:aws:iam::1:role/ReadOnly");
+    }
+
+    #[test]
+    fn validate_rejects_invalid_regex() {
+        let selector = AccountSelector {
+            matches: Some("(".into()),
+            ..Default::default()
+        };
+
+        assert!(selector.validate().is_err());
+    }
+
+    #[test]
+    fn reconcile_adds_and_removes_accounts() {
+        let mut group = Group {
+            selectors: vec![AccountSelector {
+                prefix: Some("payments-".into()),
+                roles: vec!["Administrator".into()],
+                ..Default::default()
+            }],
+            accounts: vec![
+                Account {
+                    name: "payments-prod-eu".into(),
+                    arn: "arn:aws:iam::1:role/Administrator".into(),
+                    valid_until: Some(Local::now().fixed_offset()),
+                    ..Default::default()
+                },
+                Account {
+                    name: "payments-old".into(),
+                    arn: "arn:aws:iam::9:role/Administrator".into(),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+
+        let (added, removed) = group.reconcile(&roles()).unwrap();
+
+        assert_eq!(added, vec!["payments-dev-eu"]);
+        assert_eq!(removed, vec!["payments-old"]);
+        assert_eq!(group.accounts.len(), 2);
+        assert!(group.accounts[0].valid_until.is_some());
+    }
+
+    #[test]
+    fn reconcile_keeps_accounts_without_account_names() {
+        let mut group = Group {
+            selectors: vec![AccountSelector {
+                prefix: Some("payments-".into()),
+                roles: vec!["Administrator".into()],
+                ..Default::default()
+            }],
+            accounts: vec![Account {
+                name: "payments-prod-eu".into(),
+                arn: "arn:aws:iam::1:role/Administrator".into(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        // Without a sign-in page the account ids are used as names
+        let unnamed = ::roles::roles_from_response(
+            &::roles::tests::saml_response(&["arn:aws:iam::1:role/Administrator"]),
+            None,
+        )
+        .unwrap();
+
+        assert!(group.reconcile(&unnamed).is_err());
+        assert!(group
+            .reconcile(&[role("billing-prod", "3", "Administrator")])
+            .is_err());
+        assert_eq!(group.accounts.len(), 1);
+    }
+
+    #[test]
+    fn parse_account_requires_role_arn() {
+        let account = parse_account("prod=arn:aws:iam::123456789012:role/Admin").unwrap();
+
+        assert_eq!(account.name, "prod");
+        assert_eq!(account.arn, "arn:aws:iam::123456789012:role/Admin");
+        assert!(parse_account("prod").is_err());
+        assert!(parse_account("=arn:aws:iam::123456789012:role/Admin").is_err());
+        assert!(parse_account("prod=arn:aws:iam::123456789012:user/Admin").is_err());
+    }
+
+    #[test]
+    fn profile_name_renders_template() {
+        let mut group = Group::default();
+        let account = parse_account("prod=arn:aws:iam::123456789012:role/Admin").unwrap();
+
+        assert_eq!(group.profile_name("payments", &account), "prod");
+
+        group.profile_template = Some("{group}-{account}-{account_id}-{role}".into());
+
+        assert_eq!(
+            group.profile_name("payments", &account),
+            "payments-prod-123456789012-Admin"
+        );
+
+        let malformed = Account {
+            name: "broken".into(),
+            arn: "Admin".into(),
+            ..Default::default()
+        };
+
+        assert_eq!(group.profile_name("payments", &malformed), "broken");
+    }
+
+    #[test]
+    fn expire_moved_profiles_clears_sessions() {
+        let valid_until = Some(Local::now().fixed_offset());
+        let mut group = Group {
+            profile_template: Some("{group}-{account}".into()),
+            accounts: vec![
+                Account {
+                    name: "prod".into(),
+                    arn: "arn:aws:iam::123456789012:role/Admin".into(),
+                    valid_until,
+                    ..Default::default()
+                },
+                Account {
+                    name: "dev".into(),
+                    arn: "arn:aws:iam::210987654321:role/Admin".into(),
+                    profile: Some("dev".into()),
+                    valid_until,
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+
+        let before = group.profiles("payments");
+        group.expire_moved_profiles("payments", &before);
+
+        assert!(group.accounts.iter().all(|a| a.valid_until.is_some()));
+
+        group.expire_moved_profiles("billing", &before);
+
+        assert!(group.accounts[0].valid_until.is_none());
+        assert!(group.accounts[1].valid_until.is_some());
+
+        group.region = Some("eu-central-1".into());
+        group.expire_moved_profiles("payments", &before);
+
+        assert!(group.accounts[1].valid_until.is_none());
+    }
+
+    #[test]
+    fn account_overrides_take_precedence() {
+        let group = Group {
+            session_duration: Some(3600),
+            region: Some("eu-central-1".into()),
+            profile_template: Some("{group}-{account}".into()),
+            ..Default::default()
+        };
+        let mut account = parse_account("prod=arn:aws:iam::123456789012:role/Admin").unwrap();
+
+        assert_eq!(group.session_duration_for(&account), Some(3600));
+        assert_eq!(
+            group.sts_endpoint_for(&account).unwrap(),
+            "https://sts.eu-central-1.amazonaws.com/"
+        );
+        assert_eq!(group.profile_name("payments", &account), "payments-prod");
+
+        account.session_duration = Some(900);
+        account.region = Some("us-east-1".into());
+        account.profile = Some("prod-admin".into());
+
+        assert_eq!(group.session_duration_for(&account), Some(900));
+        assert_eq!(group.region_for(&account), Some("us-east-1"));
+        assert_eq!(
+            group.sts_endpoint_for(&account).unwrap(),
+            "https://sts.us-east-1.amazonaws.com/"
+        );
+        assert_eq!(group.profile_name("payments", &account), "prod-admin");
+    }
+
+    #[test]
+    fn resolve_groups_expands_includes() {
+        let group = |includes: Vec<&str>| Group {
+            includes: includes.into_iter().map(String::from).collect(),
+            ..Default::default()
+        };
+
+        let mut cfg = Config::default("test.yml");
+        cfg.groups.insert("payments".into(), group(vec![]));
+        cfg.groups.insert("search".into(), group(vec![]));
+        cfg.groups
+            .insert("prod".into(), group(vec!["payments", "search"]));
+        cfg.groups
+            .insert("all".into(), group(vec!["prod", "payments", "all"]));
+
+        assert_eq!(
+            cfg.resolve_groups(&["all".into()]).unwrap(),
+            vec!["all", "prod", "payments", "search"]
+        );
+
+        cfg.groups.insert("broken".into(), group(vec!["missing"]));
+
+        assert!(cfg.resolve_groups(&["broken".into()]).is_err());
+        assert!(cfg.resolve_groups(&["missing".into()]).is_err());
+    }
+
+    #[test]
+    fn groups_by_idp_leaves_out_composite_groups() {
+        let group = |idp: Option<&str>, includes: Vec<&str>| Group {
+            idp: idp.map(String::from),
+            includes: includes.into_iter().map(String::from).collect(),
+            ..Default::default()
+        };
+
+        let mut cfg = Config::default("test.yml");
+        cfg.groups
+            .insert("payments".into(), group(Some("corp"), vec![]));
+        cfg.groups.insert("search".into(), group(None, vec![]));
+        cfg.groups
+            .insert("billing".into(), group(Some("corp"), vec![]));
+        cfg.groups
+            .insert("all".into(), group(None, vec!["payments", "billing"]));
+
+        let names = cfg
+            .resolve_groups(&["all".into(), "search".into()])
+            .unwrap();
+
+        assert_eq!(
+            cfg.groups_by_idp(&names),
+            vec![
+                (
+                    Some("corp".to_string()),
+                    vec!["payments".to_string(), "billing".to_string()]
+                ),
+                (None, vec!["search".to_string()]),
+            ]
+        );
+    }
+
+    #[test]
+    fn idp_returns_named_or_default_idp() {
+        let mut cfg = Config::default("test.yml");
+        cfg.idp_url = "https://default.example.com".into();
+        cfg.idps.insert(
+            "corp".into(),
+            Idp {
+                url: "https://corp.example.com".into(),
+                ..Default::default()
+            },
+        );
+
+        assert_eq!(
+            cfg.idp(Some("corp")).unwrap().url,
+            "https://corp.example.com"
+        );
+        assert!(cfg.idp(Some("missing")).is_none());
+        assert!(cfg.idp(None).is_some());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn write_atomic_keeps_symlinks_and_permissions() {
+        use std::os::unix::fs::{symlink, PermissionsExt};
+
+        let dir = env::temp_dir().join(format!("saml2aws-auto-write-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let target = dir.join("dotfiles-config.yml");
+        let link = dir.join("config.yml");
+
+        fs::write(&target, "old").unwrap();
+        fs::set_permissions(&target, fs::Permissions::from_mode(0o600)).unwrap();
+        symlink(&target, &link).unwrap();
+
+        write_atomic(link.to_str().unwrap(), b"new").unwrap();
+
+        assert!(fs::symlink_metadata(&link)
+            .unwrap()
+            .file_type()
+            .is_symlink());
+        assert_eq!(fs::read_to_string(&target).unwrap(), "new");
+        assert_eq!(
+            fs::metadata(&target).unwrap().permissions().mode() & 0o777,
+            0o600
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn idp_applies_environment_overrides() {
+        let mut cfg = Config::default("test.yml");
+        cfg.username = Some("jane".into());
+        cfg.mfa_device = Some("phone".into());
+
+        let mut vars = HashMap::new();
+        vars.insert(ENV_IDP_URL, "https://env.example.com");
+        vars.insert(ENV_USERNAME, "ci-user");
+        let idp = cfg
+            .idp_with(None, |k| vars.get(k).map(|v| v.to_string()))
+            .unwrap();
+
+        assert_eq!(idp.url, "https://env.example.com");
+        assert_eq!(idp.username.as_deref(), Some("ci-user"));
+        assert_eq!(idp.mfa_device.as_deref(), Some("phone"));
+    }
+
+    #[test]
+    fn xdg_filename_prefers_config_home() {
+        let home = Path::new("/home/jane");
+
+        assert_eq!(
+            xdg_filename(Some("/tmp/config".into()), home),
+            "/tmp/config/saml2aws-auto/config.yml"
+        );
+        assert_eq!(
+            xdg_filename(Some("".into()), home),
+            "/home/jane/.config/saml2aws-auto/config.yml"
+        );
+        assert_eq!(
+            xdg_filename(None, home),
+            "/home/jane/.config/saml2aws-auto/config.yml"
+        );
+    }
+
+    #[test]
+    fn keyring_service_is_scoped_by_idp() {
+        assert_eq!(keyring_service(None), "saml2aws-auto");
+        assert_eq!(keyring_service(Some("corp")), "saml2aws-auto/corp");
+    }
+}
@@ -0,0 +1,182 @@
+use std::convert::TryFrom;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::io::IsTerminal;
+use std::process::{Command, Stdio};
+use std::str::FromStr;
+
+use secret::Secret;
+
+/// Where the password of an IdP is read from
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum PasswordSource {
+    /// The credentials manager of the platform
+    Keyring,
+    /// An environment variable
+    Env(String),
+    /// The output of a shell command, e.g. `pass show idp`
+    Command(String),
+    /// The first line of a file
+    File(String),
+    /// Standard input, or a prompt if it is a terminal
+    Stdin,
+}
+
+impl PasswordSource {
+    /// Reads the password from the source. The keyring is read when loading the config,
+    /// so it is not supported here.
+    pub fn read(&self) -> Result<Secret, String> {
+        match self {
+            PasswordSource::Keyring => Err("The keyring is read when loading the config".into()),
+            PasswordSource::Env(var) => env::var(var)
+                .map(Secret::from)
+                .map_err(|_| format!("The environment variable {} is not set", var)),
+            PasswordSource::Command(cmd) => read_command(cmd),
+            PasswordSource::File(path) => fs::read_to_string(path)
+                .map(|s| first_line(&Secret::from(s)))
+                .map_err(|e| format!("Could not read the password from {}: {}", path, e)),
+            PasswordSource::Stdin => read_stdin(),
+        }
+    }
+}
+
+fn read_command(cmd: &str) -> Result<Secret, String> {
+    let mut command = if cfg!(windows) {
+        let mut c = Command::new("cmd");
+        c.arg("/C");
+        c
+    } else {
+        let mut c = Command::new("sh");
+        c.arg("-c");
+        c
+    };
+
+    // stdin and stderr are inherited, so password managers can ask for confirmation
+    let output = command
+        .arg(cmd)
+        .stdin(Stdio::inherit())
+        .stderr(Stdio::inherit())
+        .output()
+        .map_err(|e| format!("Could not run the password command: {}", e))?;
+
+    if !output.status.success() {
+        return Err(format!(
+            "The password command failed with {}",
+            output.status
+        ));
+    }
+
+    String::from_utf8(output.stdout)
+        .map(|s| first_line(&Secret::from(s)))
+        .map_err(|_| "The password command did not print valid UTF-8".into())
+}
+
+fn read_stdin() -> Result<Secret, String> {
+    // The prompt is written to the terminal, not to stdout
+    if io::stdin().is_terminal() {
+        return match rpassword::prompt_password("IDP Password: ") {
+            Ok(ref p) if !p.trim().is_empty() => Ok(Secret::from(p.trim().to_string())),
+            Ok(_) => Err("No password provided".into()),
+            Err(e) => Err(format!("Could not read the password: {}", e)),
+        };
+    }
+
+    let mut buf = String::new();
+    io::stdin()
+        .read_line(&mut buf)
+        .map_err(|e| format!("Could not read the password from stdin: {}", e))?;
+
+    Ok(first_line(&Secret::from(buf)))
+}
+
+/// Returns the first line of the output of a password source, which is zeroized on drop
+fn first_line(s: &Secret) -> Secret {
+    Secret::from(s.expose().lines().next().unwrap_or_default().to_string())
+}
+
+impl FromStr for PasswordSource {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, value) = match s.split_once(':') {
+            Some((kind, value)) => (kind, Some(value)),
+            None => (s, None),
+        };
+
+        match (kind, value) {
+            ("keyring", None) => Ok(PasswordSource::Keyring),
+            ("stdin", None) => Ok(PasswordSource::Stdin),
+            ("env", Some(v)) if !v.is_empty() => Ok(PasswordSource::Env(v.into())),
+            ("command", Some(v)) if !v.is_empty() => Ok(PasswordSource::Command(v.into())),
+            ("file", Some(v)) if !v.is_empty() => Ok(PasswordSource::File(v.into())),
+            _ => Err(format!(
+                "Invalid password source {}, expected one of keyring, env:VAR, command:CMD, file:PATH or stdin",
+                s
+            )),
+        }
+    }
+}
+
+impl TryFrom<String> for PasswordSource {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<PasswordSource> for String {
+    fn from(source: PasswordSource) -> Self {
+        source.to_string()
+    }
+}
+
+impl fmt::Display for PasswordSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PasswordSource::Keyring => write!(f, "keyring"),
+            PasswordSource::Env(var) => write!(f, "env:{}", var),
+            PasswordSource::Command(cmd) => write!(f, "command:{}", cmd),
+            PasswordSource::File(path) => write!(f, "file:{}", path),
+            PasswordSource::Stdin => write!(f, "stdin"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_password_sources() {
+        for s in &[
+            "keyring",
+            "stdin",
+            "env:IDP_PASSWORD",
+            "command:pass show idp",
+            "file:/run/secrets/idp",
+        ] {
+            assert_eq!(&s.parse::<PasswordSource>().unwrap().to_string(), s);
+        }
+
+        assert!("env:".parse::<PasswordSource>().is_err());
+        assert!("keyring:foo".parse::<PasswordSource>().is_err());
+        assert!("vault".parse::<PasswordSource>().is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn reads_first_line_of_command_output() {
+        let source: PasswordSource = "command:printf 'secret pass\\nignored'".parse().unwrap();
+
+        assert_eq!(source.read().unwrap().expose(), "secret pass");
+        assert!("command:false"
+            .parse::<PasswordSource>()
+            .unwrap()
+            .read()
+            .is_err());
+    }
+}
@@ -0,0 +1,168 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use chrono::prelude::*;
+use dirs;
+use serde_yaml;
+
+use super::{write_atomic, Config};
+
+/// Expiration of the sessions of a config, by group and account name
+type Sessions = BTreeMap<String, BTreeMap<String, DateTime<FixedOffset>>>;
+
+/// Session state that changes on every refresh. It is kept out of the config file, so
+/// the config only changes when the settings do.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct State {
+    /// Sessions by the path of the config file they belong to
+    #[serde(default)]
+    sessions: BTreeMap<String, Sessions>,
+}
+
+/// Returns the directory state that is not part of the config is kept in, if the platform
+/// has one
+pub fn state_directory() -> Option<PathBuf> {
+    let mut path = dirs::state_dir().or_else(dirs::data_local_dir)?;
+    path.push("saml2aws-auto");
+    Some(path)
+}
+
+/// Returns the path of the state file. Without a state directory, the state is kept next
+/// to the config file.
+pub fn state_filename(config_directory: &Path) -> String {
+    let path = match state_directory() {
+        Some(dir) => dir.join("state.yml"),
+        None => config_directory.join(".saml2aws-auto-state.yml"),
+    };
+
+    path.to_string_lossy().into_owned()
+}
+
+fn load(path: &str) -> Result<State, io::Error> {
+    if !Path::new(path).exists() {
+        return Ok(State::default());
+    }
+
+    let buf = fs::read_to_string(path)?;
+    serde_yaml::from_str(&buf).map_err(io::Error::other)
+}
+
+/// Returns the key of the config in the state file. Configs are identified by their
+/// absolute path, since several of them can be used with `--config`.
+fn config_key(cfg: &Config) -> String {
+    match fs::canonicalize(&cfg.filename) {
+        Ok(path) => format!("{}", path.display()),
+        Err(_) => cfg.filename.clone(),
+    }
+}
+
+impl Config {
+    /// Sets the session expiration of all accounts found in the state file
+    pub fn load_state(&mut self) -> Result<(), io::Error> {
+        let key = config_key(self);
+        let mut state = load(&self.state_filename)?;
+
+        let sessions = match state.sessions.remove(&key) {
+            Some(s) => s,
+            None => return Ok(()),
+        };
+
+        for (name, group) in self.groups.iter_mut() {
+            let group_sessions = match sessions.get(name) {
+                Some(s) => s,
+                None => continue,
+            };
+
+            for account in &mut group.accounts {
+                if let Some(valid_until) = group_sessions.get(&account.name) {
+                    account.valid_until = Some(*valid_until);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Writes the session expiration of all accounts to the state file, keeping the
+    /// sessions of other config files.
+    pub fn save_state(&self) -> Result<(), io::Error> {
+        let mut state = load(&self.state_filename)?;
+
+        let sessions: Sessions = self
+            .groups
+            .iter()
+            .map(|(name, group)| {
+                let accounts = group
+                    .accounts
+                    .iter()
+                    .filter_map(|a| a.valid_until.map(|v| (a.name.clone(), v)))
+                    .collect();
+
+                (name.clone(), accounts)
+            })
+            .filter(|(_, accounts): &(String, BTreeMap<_, _>)| !accounts.is_empty())
+            .collect();
+
+        state.sessions.insert(config_key(self), sessions);
+
+        if let Some(dir) = Path::new(&self.state_filename).parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        let buf = serde_yaml::to_string(&state).map_err(io::Error::other)?;
+        write_atomic(&self.state_filename, buf.as_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use config::{parse_account, Group, Storage};
+    use std::env;
+    use std::process;
+
+    #[test]
+    fn sessions_are_saved_to_the_state_file() {
+        let dir = env::temp_dir().join(format!("saml2aws-auto-state-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let config_filename = dir.join("config.yml");
+        let mut cfg = Config::default(config_filename.to_str().unwrap());
+        cfg.state_filename = format!("{}", dir.join("state/state.yml").display());
+
+        let mut account = parse_account("prod=arn:aws:iam::123456789012:role/Admin").unwrap();
+        account.valid_until = Some(Local::now().fixed_offset());
+        cfg.groups.insert(
+            "payments".into(),
+            Group {
+                session_duration: None,
+                sts_endpoint: None,
+                min_remaining: None,
+                region: None,
+                profile_template: None,
+                idp: None,
+                storage: Storage::Plaintext,
+                selectors: vec![],
+                includes: vec![],
+                accounts: vec![account.clone()],
+            },
+        );
+
+        cfg.save().unwrap();
+
+        let written = fs::read_to_string(&config_filename).unwrap();
+        assert!(!written.contains("valid_until"));
+
+        cfg.groups.get_mut("payments").unwrap().accounts[0].valid_until = None;
+        cfg.load_state().unwrap();
+
+        assert_eq!(
+            cfg.groups["payments"].accounts[0].valid_until,
+            account.valid_until
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
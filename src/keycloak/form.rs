@@ -1,5 +1,7 @@
 use scraper::{node::Element, ElementRef, Html, Selector};
 
+use secret::Secret;
+
 #[derive(Debug, PartialEq)]
 pub enum FormMethod {
     GET,
@@ -71,7 +73,7 @@ impl FormInfo {
     }
 }
 
-pub fn extract_saml_response(doc: &Html) -> Option<String> {
+pub fn extract_saml_response(doc: &Html) -> Option<Secret> {
     let elements: Vec<ElementRef> = doc
         .select(&Selector::parse("input[name=\"SAMLResponse\"]").unwrap())
         .collect();
@@ -80,5 +82,8 @@ pub fn extract_saml_response(doc: &Html) -> Option<String> {
         return None;
     }
 
-    elements[0].value().attr("value").map(|v| v.into())
+    elements[0]
+        .value()
+        .attr("value")
+        .map(|v| Secret::from(v.to_string()))
 }
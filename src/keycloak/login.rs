@@ -8,18 +8,23 @@ use super::form::{extract_saml_response, FormInfo};
 use super::mfa::get_totp_form;
 use super::{KeycloakError, KeycloakErrorKind};
 use client;
+use logging;
+use secret::Secret;
 
 pub fn get_assertion_response(
     cookie_jar: &mut CookieJar,
     url: &str,
     username: &str,
-    password: &str,
+    password: &Secret,
     mfa_device: Option<&str>,
-    token: &str,
+    token: &Secret,
     do_aws_page_request: bool,
-) -> Result<(String, Option<String>), KeycloakError> {
+) -> Result<(Secret, Option<String>), KeycloakError> {
+    let _span = logging::span("login");
     trace!("get_assertion_response.start");
-    let client = client::get_proxied_client_builder().build().unwrap();
+    let client = client::get_proxied_client_builder()
+        .and_then(|cb| cb.build().map_err(|e| e.to_string()))
+        .map_err(|e| KeycloakError::new(KeycloakErrorKind::Http, &e))?;
     let mut doc = get_login_page(&client, cookie_jar, url)?;
     let form = get_login_form(&doc);
 
@@ -64,13 +69,13 @@ fn do_login_flow(
     cookie_jar: &mut CookieJar,
     login_url: &str,
     username: &str,
-    password: &str,
+    password: &Secret,
     mfa_device: Option<&str>,
-    token: &str,
+    token: &Secret,
 ) -> Result<String, KeycloakError> {
     trace!("do_login_flow.start");
     // Submit User+Pass
-    let params = [("username", username), ("password", password)];
+    let params = [("username", username), ("password", password.expose())];
 
     trace!("do_login_flow.submit_form");
     let doc = submit_form(&client, cookie_jar, login_url, &params)?;
@@ -85,6 +90,7 @@ fn do_login_flow(
     }
 
     // Submit TOTP
+    let token = token.expose().trim();
     let mut params = vec![("otp", token), ("totp", token)];
 
     if let Some(mfa_device) = mfa_device {
@@ -102,6 +108,7 @@ fn do_login_flow(
         }
     }
     trace!("do_login_flow.submit_form_totp");
+    let _span = logging::span("totp_submit");
     let doc = submit_form(&client, cookie_jar, &totp.action, &params)?;
 
     Ok(doc)
@@ -119,11 +126,7 @@ pub fn submit_form(
         .collect::<Vec<String>>()
         .join("; ");
 
-    let res = client
-        .post(url)
-        .form(&params)
-        .header("Cookie", cookie)
-        .send()
+    let res = client::send_once(client.post(url).form(&params).header("Cookie", cookie))
         .map_err(|e| KeycloakError::new(KeycloakErrorKind::Http, &e.to_string()))?;
 
     // Then we add cookies in the jar given the response
@@ -178,16 +181,12 @@ pub fn get_login_page(
     trace!("get_login_page.cookie={}", &cookie);
 
     trace!("get_login_page.send");
-    let res = client
-        .get(url)
-        .header("Cookie", cookie)
-        .send()
-        .map_err(|e| {
-            trace!("get_login_page.map_err");
-            error!("get_login_page: {:?}", e);
+    let res = client::send(client.get(url).header("Cookie", cookie)).map_err(|e| {
+        trace!("get_login_page.map_err");
+        error!("get_login_page: {:?}", e);
 
-            KeycloakError::new(KeycloakErrorKind::Http, &e.to_string())
-        })?;
+        KeycloakError::new(KeycloakErrorKind::Http, &e.to_string())
+    })?;
 
     // Then we add cookies in the jar given the response
     trace!("get_login_page.cookies");
@@ -236,7 +235,7 @@ pub fn get_login_form(document: &str) -> Result<FormInfo, KeycloakError> {
     Ok(form)
 }
 
-pub fn get_intermediate_response(document: &str) -> Result<(String, FormInfo), KeycloakError> {
+pub fn get_intermediate_response(document: &str) -> Result<(Secret, FormInfo), KeycloakError> {
     trace!("get_intermediate_response.start");
 
     if document.contains("Invalid authenticator code") {
@@ -281,9 +280,9 @@ pub fn submit_saml_response_form(
     client: &reqwest::blocking::Client,
     cookie_jar: &mut CookieJar,
     url: &str,
-    response: &str,
+    response: &Secret,
 ) -> Result<String, KeycloakError> {
-    let params = [("SAMLResponse", response)];
+    let params = [("SAMLResponse", response.expose())];
 
     let cookie = cookie_jar
         .iter()
@@ -292,11 +291,7 @@ pub fn submit_saml_response_form(
         .join("; ");
     trace!("submit_saml_response_form.cookie={}", &cookie);
 
-    let res = client
-        .post(url)
-        .form(&params)
-        .header("Cookie", cookie)
-        .send()
+    let res = client::send_once(client.post(url).form(&params).header("Cookie", cookie))
         .map_err(|e| KeycloakError::new(KeycloakErrorKind::Http, &e.to_string()))?;
 
     // Then we add cookies in the jar given the response
@@ -29,6 +29,15 @@ pub enum KeycloakErrorKind {
     TermsAndConditionsNotAccepted,
 }
 
+impl KeycloakErrorKind {
+    /// Returns whether logging in again with the same password and MFA token fails as well
+    pub fn is_unrecoverable(&self) -> bool {
+        *self == KeycloakErrorKind::InvalidCredentials
+            || *self == KeycloakErrorKind::InvalidToken
+            || *self == KeycloakErrorKind::PasswordUpdateRequired
+    }
+}
+
 impl KeycloakError {
     pub fn new(kind: KeycloakErrorKind, message: &str) -> Self {
         KeycloakError {
@@ -0,0 +1,70 @@
+//! Logging in to Keycloak with SAML and refreshing AWS credentials, as done by the
+//! saml2aws-auto command line tool.
+//!
+//! The IdP login is `get_assertion_response`, the roles of the assertion are read with
+//! `parse_assertion` and assumed with `assume_role`. `refresh_group` combines them to
+//! refresh the groups of a `Config` with a `Session`, returning the result of every
+//! account instead of printing it.
+//!
+//! Requests use the proxies of the environment unless `ClientSettings` are applied.
+//! Messages are logged with the `log` crate, tagged with the spans they were logged in
+//! (see `current_spans`), and `redact` masks the secrets they may contain.
+
+extern crate chrono;
+extern crate regex;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_json;
+extern crate serde_xml_rs;
+extern crate serde_yaml;
+
+#[macro_use]
+extern crate log;
+
+extern crate base64;
+extern crate cookie;
+extern crate dirs;
+extern crate ini;
+extern crate keyring;
+extern crate openssl;
+extern crate reqwest;
+extern crate rpassword;
+extern crate scraper;
+extern crate url;
+extern crate zeroize;
+
+mod aws;
+mod cache;
+mod client;
+pub mod config;
+mod keycloak;
+mod logging;
+mod redact;
+mod refresh;
+mod roles;
+mod saml;
+mod secret;
+
+pub use aws::assume_role::{assume_role, AssumeRoleError};
+pub use aws::credentials::load_credentials_file;
+pub use aws::xml::Credentials;
+pub use cache::{CachedCredentials, CredentialCache};
+pub use client::{
+    get_proxied_client_builder as http_client_builder, send as send_with_retries, ClientSettings,
+};
+pub use config::{
+    default_filename, load_or_default, Account, AccountSelector, Config, Group, Idp,
+    PasswordSource, Storage,
+};
+pub use keycloak::login::get_assertion_response;
+pub use keycloak::{KeycloakError, KeycloakErrorKind};
+pub use logging::{current_spans, span, Span};
+pub use redact::redact;
+pub use refresh::{
+    refresh_group, sessions_valid, AccountRefresh, GroupOutcome, GroupRefresh, GroupRefreshError,
+    RefreshError, RefreshOptions, Session,
+};
+pub use roles::{fetch_roles, RoleInfo};
+pub use saml::{parse_assertion, Assertion, AssertionError, Role};
+pub use secret::Secret;
@@ -0,0 +1,69 @@
+use std::cell::RefCell;
+use std::time::Instant;
+
+thread_local! {
+    /// Names of the spans the current thread is in, from the outermost to the innermost
+    static SPANS: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+}
+
+/// A unit of work, e.g. a login or an STS call. Messages logged while the span is alive
+/// are tagged with its name in the log file, and its duration is logged when it is
+/// dropped. Spans are tracked per thread, threads spawned within a span continue it with
+/// `enter_spans`.
+pub struct Span {
+    name: String,
+    start: Instant,
+}
+
+/// Enters a span, which lasts until the returned value is dropped
+pub fn span(name: &str) -> Span {
+    SPANS.with(|spans| spans.borrow_mut().push(name.into()));
+    trace!("{} started", name);
+
+    Span {
+        name: name.into(),
+        start: Instant::now(),
+    }
+}
+
+/// Returns the spans the current thread is in, so a spawned thread can continue them
+pub fn current_spans() -> Vec<String> {
+    SPANS.with(|spans| spans.borrow().clone())
+}
+
+/// Continues the spans of the thread that spawned the current one, see `current_spans`
+pub fn enter_spans(parents: Vec<String>) {
+    SPANS.with(|spans| *spans.borrow_mut() = parents);
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        debug!(
+            "{} finished after {}ms",
+            self.name,
+            self.start.elapsed().as_millis()
+        );
+        SPANS.with(|spans| spans.borrow_mut().pop());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spawned_threads_continue_spans() {
+        let _span = span("group=payments");
+        let parents = current_spans();
+
+        let spans = std::thread::spawn(move || {
+            enter_spans(parents);
+            let _span = span("account=payments-prod");
+            current_spans()
+        })
+        .join()
+        .unwrap();
+
+        assert_eq!(spans, vec!["group=payments", "account=payments-prod"]);
+    }
+}
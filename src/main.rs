@@ -1,104 +1,213 @@
 extern crate chrono;
 extern crate clap;
 extern crate crossterm;
-extern crate regex;
 #[macro_use]
 extern crate serde_derive;
-extern crate serde_xml_rs;
+extern crate serde_json;
 extern crate serde_yaml;
 
 #[macro_use]
 extern crate log;
 extern crate fern;
 
-extern crate base64;
-extern crate cookie;
-extern crate dirs;
-extern crate ini;
-extern crate keyring;
+extern crate openssl;
 extern crate openssl_probe;
 extern crate reqwest;
 extern crate rpassword;
-extern crate scraper;
+extern crate saml2aws_auto;
 extern crate tabled;
-extern crate url;
+extern crate zip;
 
-mod aws;
 mod cli;
-pub mod client;
-mod config;
-mod groups;
-mod keycloak;
-mod refresh;
-mod saml;
-mod update;
+
+use saml2aws_auto::{config, ClientSettings};
 
 use clap::Parser;
 use cli::Cli;
 use crossterm::style::Stylize;
-use log::LevelFilter;
+use std::io::IsTerminal;
 
 fn main() {
     openssl_probe::init_ssl_cert_env_vars();
 
     let cli = Cli::parse();
 
-    let level = match cli.verbose {
-        false => None,
-        true => Some(LevelFilter::Trace),
-    };
-
     let config_path: String = match cli.config {
-        Some(s) => s.to_owned(),
-        None => config::default_filename(),
+        Some(ref s) => s.to_owned(),
+        None => cli::configure::locate_default_filename(),
     };
 
-    if let Some(level) = level {
-        fern::Dispatch::new()
-            .format(|out, message, record| {
-                out.finish(format_args!(
-                    "[{}][{}] {}",
-                    record.level(),
-                    record.target(),
-                    message
-                ))
-            })
-            .level(LevelFilter::Off)
-            .level_for("saml2aws_auto", level)
-            .chain(std::io::stdout())
-            .apply()
-            .unwrap();
+    let log_file = cli
+        .log_file
+        .clone()
+        .or_else(|| config::log_file(&config_path));
+
+    if let Err(e) = cli::logging::init(cli.verbose, log_file.as_deref()) {
+        eprintln!(
+            "{}: Could not write to the log file: {}",
+            "WARNING".yellow(),
+            e
+        );
+    }
+    debug!("saml2aws-auto {}", env!("CARGO_PKG_VERSION"));
+
+    let tls = tls_settings(&cli, config::tls(&config_path));
+    if tls.insecure {
+        eprintln!(
+            "{}: TLS certificates are not validated. Only use this with test IdPs.",
+            "WARNING".yellow()
+        );
+    }
+
+    ClientSettings {
+        proxy: config::proxy(&config_path),
+        tls,
+        http: config::http(&config_path).unwrap_or_default(),
     }
+    .apply();
 
-    // Check for a new version
-    if let Ok(update::VersionComparison::HasNewer) =
-        update::compare_version(env!("CARGO_PKG_VERSION"))
+    // Commands whose output is consumed by other programs
+    let quiet = cli.command.prints_data();
+
+    let update_check = config::update_check(&config_path).unwrap_or_default();
+
+    // Check for a new version, unless the output is redirected (e.g. by groups export) or
+    // the check is disabled
+    if !std::io::stdout().is_terminal() || !std::io::stdin().is_terminal() || quiet {
+        trace!("skipping version check, not running interactively");
+    } else if let cli::Commands::SelfUpdate(_) = cli.command {
+        trace!("skipping version check, updating");
+    } else if update_check.enabled == Some(false)
+        || std::env::var_os(cli::update::ENV_NO_UPDATE_CHECK).is_some()
+    {
+        trace!("skipping version check, it is disabled");
+    } else if let Ok(cli::update::VersionComparison::HasNewer) =
+        cli::update::compare_version(env!("CARGO_PKG_VERSION"), update_check.url.as_deref())
     {
         println!(
             "\n\t{}",
             "A new version of saml2aws-auto is available".green()
         );
-        println!("\tIf you want to enjoy the greatest and latest features, make sure to update\n\tyour installation of saml2aws-auto.");
+        println!(
+            "\tIf you want to enjoy the greatest and latest features, run {}.",
+            "saml2aws-auto self-update".yellow()
+        );
         println!("");
     }
 
-    if !config::check_or_interactive_create(&config_path, cli.skip_password_manager) {
+    // Updating does not need a config
+    if let cli::Commands::SelfUpdate(ref args) = cli.command {
+        if !cli::self_update::command(args, &update_check) {
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    // Prompts or the setup would end up in the output or wait for input that never comes
+    if quiet {
+        if !cli::configure::check_non_interactive(&config_path) {
+            std::process::exit(1);
+        }
+    } else if !cli::configure::check_or_interactive_create(&config_path, cli.skip_password_manager)
+    {
         return;
     }
 
     match cli.command {
-        cli::Commands::Configure => {
-            config::interactive_create(config::load_or_default(&config_path).unwrap());
+        cli::Commands::Configure { idp } => {
+            let cfg = config::load_or_default(&config_path).unwrap();
+
+            match idp {
+                Some(name) => cli::configure::interactive_create_idp(cfg, &name),
+                None => cli::configure::interactive_create(cfg),
+            }
         }
-        cli::Commands::Groups { command } => groups::command(
+        cli::Commands::Groups { command } => cli::groups::command(
             &mut config::load_or_default(&config_path).unwrap(),
             &command,
         ),
         cli::Commands::Refresh(args) => {
-            refresh::command(&mut config::load_or_default(&config_path).unwrap(), &args)
+            cli::refresh::command(&mut config::load_or_default(&config_path).unwrap(), &args)
+        }
+        cli::Commands::Roles { command } => {
+            if !cli::roles::command(&config::load_or_default(&config_path).unwrap(), &command) {
+                std::process::exit(1);
+            }
+        }
+        cli::Commands::Status(args) => {
+            if !cli::status::command(&config::load_or_default(&config_path).unwrap(), &args) {
+                std::process::exit(1);
+            }
+        }
+        cli::Commands::Exec(args) => {
+            let code = cli::exec::command(&config::load_or_default(&config_path).unwrap(), &args);
+            std::process::exit(code);
+        }
+        cli::Commands::CredentialProcess { profile } => {
+            if !cli::exec::credential_process(
+                &config::load_or_default(&config_path).unwrap(),
+                &profile,
+            ) {
+                std::process::exit(1);
+            }
         }
         cli::Commands::Version => {
             println!("saml2aws-auto {}", env!("CARGO_PKG_VERSION"));
         }
+        cli::Commands::SelfUpdate(_) => unreachable!(),
+    }
+}
+
+/// Returns the TLS settings of the config file, overridden by the command line. A client
+/// certificate given on the command line replaces the one of the config file.
+fn tls_settings(cli: &Cli, config: Option<config::TlsConfig>) -> config::TlsConfig {
+    let mut tls = config.unwrap_or_default();
+    if let Some(ref path) = cli.ca_bundle {
+        tls.ca_bundle = Some(path.clone());
+    }
+    if cli.client_cert.is_some() || cli.client_pkcs12.is_some() {
+        tls.client_cert = cli.client_cert.clone();
+        tls.client_key = cli.client_key.clone();
+        tls.client_pkcs12 = cli.client_pkcs12.clone();
+    }
+    if cli.insecure {
+        tls.insecure = true;
+    }
+    tls
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn command_line_overrides_tls_settings() {
+        let config = config::TlsConfig {
+            ca_bundle: Some("config-ca.pem".into()),
+            client_pkcs12: Some("config.p12".into()),
+            ..Default::default()
+        };
+
+        let cli = Cli::parse_from(["saml2aws-auto", "version"]);
+        assert_eq!(tls_settings(&cli, Some(config.clone())), config);
+
+        let cli = Cli::parse_from([
+            "saml2aws-auto",
+            "--ca-bundle",
+            "ca.pem",
+            "--client-cert",
+            "client.crt",
+            "--client-key",
+            "client.key",
+            "--insecure",
+            "version",
+        ]);
+        let tls = tls_settings(&cli, Some(config));
+
+        assert_eq!(tls.ca_bundle.as_deref(), Some("ca.pem"));
+        assert_eq!(tls.client_cert.as_deref(), Some("client.crt"));
+        assert_eq!(tls.client_key.as_deref(), Some("client.key"));
+        assert_eq!(tls.client_pkcs12, None);
+        assert!(tls.insecure);
     }
 }
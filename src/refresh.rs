@@ -1,249 +1,366 @@
 use std::error::Error;
 
 use chrono::prelude::*;
+use chrono::Duration;
 use std::collections::HashMap;
 use std::fmt;
 use std::str::FromStr;
+use std::sync::Arc;
 use std::thread;
-use tabled::settings::object::Columns;
-use tabled::settings::{Alignment, Modify, Style};
-use tabled::{Table, Tabled};
 
 use aws::assume_role::assume_role;
 use aws::credentials::load_credentials_file;
 use aws::xml::Credentials;
-use config::prompt;
+use cache::CredentialCache;
 use cookie::CookieJar;
-use crossterm::style::Stylize;
 use keycloak::login::get_assertion_response;
-use keycloak::KeycloakErrorKind;
+use keycloak::{KeycloakError, KeycloakErrorKind};
+use logging;
+use roles::{roles_from_response, RoleInfo};
 use saml::parse_assertion;
+use secret::Secret;
 
 use config;
+use config::Storage;
+
+/// Settings of a refresh that are not part of the config
+#[derive(Debug, Clone, Default)]
+pub struct RefreshOptions {
+    /// Refreshes accounts even if their session is still valid
+    pub force: bool,
+    /// Seconds of remaining session lifetime below which a session is refreshed. Overrides
+    /// the setting of the groups.
+    pub min_remaining: Option<i64>,
+}
 
-use crate::cli::RefreshArgs;
-
-pub fn command(cfg: &mut config::Config, args: &RefreshArgs) {
-    let force = args.force;
+/// A login to an IdP. Any number of groups of the IdP can be refreshed with it, so the
+/// MFA token is only needed once.
+pub struct Session {
+    idp_url: String,
+    mfa_device: Option<String>,
+    username: String,
+    // Shared with the refresh threads of all accounts, so they are never copied
+    password: Arc<Secret>,
+    mfa: Arc<Secret>,
+    cookie_jar: CookieJar,
+
+    /// Roles of the user if they were requested, used to synchronise groups with selectors
+    pub roles: Option<Result<Vec<RoleInfo>, Box<dyn Error>>>,
+
+    /// Credentials of accounts refreshed in this session, by role ARN. Accounts that are
+    /// part of several groups are only refreshed once.
+    refreshed: HashMap<String, (Credentials, Option<DateTime<FixedOffset>>)>,
+}
 
-    let cfg_username = cfg.username.as_ref().unwrap();
-    let username = args.username.as_deref().unwrap_or(cfg_username);
+impl Session {
+    /// Logs in to the IdP. If `list_roles` is set, the roles of the user are listed, which
+    /// requires the account names from the AWS sign-in page.
+    pub fn login(
+        idp: &config::Idp,
+        username: &str,
+        password: Secret,
+        mfa: Secret,
+        list_roles: bool,
+    ) -> Result<Session, KeycloakError> {
+        let mut cookie_jar = CookieJar::new();
 
-    let password = match &args.password {
-        Some(s) => s.to_string(),
-        None => cfg.password.as_ref().expect("Password could not be found, please run saml2aws-auto configure or provide a password by supplying the --password flag").clone(),
-    };
+        // Do an initial login to fill our cookie jar
+        trace!("Session::login.before");
+        let (saml_response, web_response) = get_assertion_response(
+            &mut cookie_jar,
+            &idp.url,
+            username,
+            &password,
+            idp.mfa_device.as_deref(),
+            &mfa,
+            list_roles,
+        )?;
+        trace!("Session::login.success");
+        trace!("Session::login.cookie_jar={:?}", cookie_jar);
+
+        let roles = match list_roles {
+            true => Some(roles_from_response(&saml_response, web_response.as_deref())),
+            false => None,
+        };
 
-    // If all credentials are valid, skip refresh entirely
-    if cfg
-        .groups
-        .iter_mut()
-        .filter(|(name, _)| args.groups.iter().any(|n| n == *name))
-        .map(|(_, group)| group.accounts.iter().all(|a| a.session_valid()))
-        .all(|b| b)
-        && !force
-    {
-        println!("Nothing to refresh, all credentials are valid. Use --force to overwrite.");
-        return;
+        Ok(Session {
+            idp_url: idp.url.clone(),
+            mfa_device: idp.mfa_device.clone(),
+            username: username.into(),
+            password: Arc::new(password),
+            mfa: Arc::new(mfa),
+            cookie_jar,
+            roles,
+            refreshed: HashMap::new(),
+        })
     }
+}
 
-    {
-        let mfa = match &args.mfa {
-            Some(m) => m.to_string(),
-            None => {
-                debug!("mfa flag not set, no valid session");
-                prompt("MFA Token", Some("000000"), false).unwrap()
-            }
-        };
+/// Result of refreshing a group
+#[derive(Debug)]
+pub struct GroupRefresh {
+    /// Names of the accounts added to the group because they match its selectors
+    pub added: Vec<String>,
+    /// Names of the accounts removed from the group because they no longer match its
+    /// selectors
+    pub removed: Vec<String>,
+    /// Why the group could not be synchronised with the roles of the IdP
+    pub sync_error: Option<String>,
+    pub outcome: GroupOutcome,
+}
 
-        let mut cookie_jar = CookieJar::new();
+#[derive(Debug)]
+pub enum GroupOutcome {
+    /// The group has no accounts, e.g. because it is a composite group
+    Empty,
+    /// All accounts of the group are disabled
+    AllDisabled,
+    /// All enabled accounts have valid sessions
+    Valid,
+    /// The enabled accounts were refreshed, unless they had a valid session
+    Refreshed(Vec<Result<AccountRefresh, RefreshError>>),
+}
 
-        {
-            // Do an initial login to fill our cookie jar
-            trace!("command.initial_login.before");
-            match get_assertion_response(
-                &mut cookie_jar,
-                &cfg.idp_url,
-                username,
-                &password,
-                cfg.mfa_device.as_deref(),
-                &mfa.trim(),
-                false,
-            ) {
-                Ok(r) => r,
-                Err(e) => {
-                    if e.kind == KeycloakErrorKind::InvalidCredentials
-                        || e.kind == KeycloakErrorKind::InvalidToken
-                        || e.kind == KeycloakErrorKind::PasswordUpdateRequired
-                    {
-                        println!(
-                            "\n{} Cannot recover from error:\n\n\t{}\n",
-                            "!".red(),
-                            e.to_string().red(),
-                        );
-                    }
+/// Result of refreshing a single account
+#[derive(Debug)]
+pub struct AccountRefresh {
+    /// The account with the expiration of its new session
+    pub account: config::Account,
+    /// Name of the profile the credentials were stored as
+    pub profile: String,
+    /// New credentials, or none if the session was still valid
+    pub credentials: Option<Credentials>,
+    pub renewed: bool,
+}
 
-                    return;
-                }
-            };
-            trace!("command.initial_login.success");
+/// Why a group could not be refreshed. Failures of single accounts are part of the
+/// `GroupOutcome` instead.
+#[derive(Debug)]
+pub enum GroupRefreshError {
+    /// The group is not part of the config
+    UnknownGroup(String),
+    /// The credential cache of a group with encrypted storage could not be read or written
+    CredentialCache(Box<dyn Error>),
+    /// The AWS credentials file could not be read or written
+    CredentialsFile(Box<dyn Error>),
+}
+
+impl fmt::Display for GroupRefreshError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            GroupRefreshError::UnknownGroup(ref name) => {
+                write!(f, "The group {} does not exist", name)
+            }
+            GroupRefreshError::CredentialCache(ref e) => {
+                write!(f, "The credential cache could not be updated: {}", e)
+            }
+            GroupRefreshError::CredentialsFile(ref e) => {
+                write!(f, "The AWS credentials file could not be updated: {}", e)
+            }
         }
+    }
+}
 
-        trace!("command.cookie_jar={:?}", cookie_jar);
+impl Error for GroupRefreshError {}
+
+/// Returns true if all enabled accounts of the groups have a valid session
+pub fn sessions_valid(cfg: &config::Config, options: &RefreshOptions, groups: &[String]) -> bool {
+    cfg.groups
+        .iter()
+        .filter(|(name, _)| groups.contains(name))
+        .all(|(_, group)| {
+            let min_remaining = group.refresh_threshold(options.min_remaining);
+            group
+                .enabled_accounts()
+                .all(|a| a.session_valid(min_remaining))
+        })
+}
 
-        for (group_name, group) in cfg
-            .groups
-            .iter_mut()
-            .filter(|(name, _)| args.groups.iter().any(|n| n == *name))
-        {
-            if group.accounts.is_empty() {
-                debug!("group.accounts len is 0");
-
-                println!(
-                    "Nothing to refresh. Group {} is empty.",
-                    group_name.as_str().yellow(),
-                );
-                continue;
-            }
+/// Refreshes the accounts of a group with the session, which must be a login to the IdP of
+/// the group. The credentials are written to the AWS credentials file or the credential
+/// cache, and the expiration of the sessions is updated in the config, which is not saved.
+pub fn refresh_group(
+    cfg: &mut config::Config,
+    session: &mut Session,
+    group_name: &str,
+    options: &RefreshOptions,
+) -> Result<GroupRefresh, GroupRefreshError> {
+    let _span = logging::span(&format!("group={}", group_name));
+    let config_dir = cfg.directory();
+
+    let group = cfg
+        .groups
+        .get_mut(group_name)
+        .ok_or_else(|| GroupRefreshError::UnknownGroup(group_name.into()))?;
+
+    let mut result = GroupRefresh {
+        added: vec![],
+        removed: vec![],
+        sync_error: None,
+        outcome: GroupOutcome::Valid,
+    };
 
-            if group.accounts.iter().all(|a| a.session_valid()) && !force {
-                println!(
-                "Nothing to refresh. All accounts have valid sessions. Use --force to overwrite.");
-                continue;
+    if let Some(Ok(ref roles)) = session.roles {
+        match group.reconcile(roles) {
+            Ok((added, removed)) => {
+                result.added = added;
+                result.removed = removed;
             }
+            Err(e) => result.sync_error = Some(e),
+        }
+    }
 
-            trace!("command.looping_through_accounts");
-
-            let mut threads: Vec<
-                thread::JoinHandle<Result<(RefreshAccountOutput, CookieJar), RefreshError>>,
-            > = vec![];
-
-            for account in &group.accounts {
-                let mfa = mfa.clone();
-                let password = password.clone();
-                let username = username.to_string();
-                let idp_url = cfg.idp_url.clone();
-                let session_duration = group.session_duration;
-                let sts_endpoint = group.sts_endpoint.clone();
-                let cookie_jar = cookie_jar.clone();
-                let account = account.clone();
-                let mfa_device = cfg.mfa_device.clone();
-
-                threads.push(thread::spawn(move || {
-                    refresh_account(
-                        session_duration,
-                        &account,
-                        cookie_jar,
-                        &idp_url,
-                        &username,
-                        &password,
-                        mfa_device.as_deref(),
-                        &mfa,
-                        force,
-                        sts_endpoint,
-                    )
-                }));
-            }
+    let min_remaining = group.refresh_threshold(options.min_remaining);
 
-            let mut accounts: HashMap<String, Option<DateTime<FixedOffset>>> = HashMap::new();
+    if group.accounts.is_empty() {
+        debug!("group.accounts len is 0");
+        result.outcome = GroupOutcome::Empty;
+        return Ok(result);
+    }
 
-            let (mut credentials_file, filepath) = load_credentials_file().unwrap();
+    if group.enabled_accounts().next().is_none() {
+        result.outcome = GroupOutcome::AllDisabled;
+        return Ok(result);
+    }
 
-            #[derive(Debug, Tabled)]
-            struct TableRefreshedAccount {
-                #[tabled(rename = "Account Name")]
-                account_name: String,
-                #[tabled(rename = "Refreshed")]
-                refreshed: String,
-                #[tabled(rename = "Result")]
-                expiration: String,
-            }
+    if !group
+        .enabled_accounts()
+        .any(|a| options.force || !a.session_valid(min_remaining))
+    {
+        return Ok(result);
+    }
 
-            let outputs: Vec<TableRefreshedAccount> = threads
-                .into_iter()
-                .map(|t| match t.join() {
-                    Ok(res) => match res {
-                        Ok((output, _)) => {
-                            if let Some(credentials) = output.credentials {
-                                credentials_file
-                                    .with_section(Some(output.account.name.as_str()))
-                                    .set("aws_access_key_id", credentials.access_key_id.as_str())
-                                    .set(
-                                        "aws_secret_access_key",
-                                        credentials.secret_access_key.as_str(),
-                                    )
-                                    .set("aws_session_token", credentials.session_token.as_str())
-                                    .set("expiration", credentials.expiration.as_str());
-                            }
-                            accounts.insert(output.account.arn, output.account.valid_until);
-
-                            let now = Local::now();
-
-                            let expiration = format!(
-                                "valid for {} minutes",
-                                format!(
-                                    "{}",
-                                    output
-                                        .account
-                                        .valid_until
-                                        .unwrap()
-                                        .signed_duration_since(now)
-                                        .num_minutes()
-                                )
-                                .green()
-                            );
-
-                            TableRefreshedAccount {
-                                account_name: output.account.name,
-                                refreshed: if output.renewed {
-                                    "✓".green().to_string()
-                                } else {
-                                    "⨯".bold().red().to_string()
-                                },
-                                expiration,
-                            }
-                        }
-                        Err(ref e) => TableRefreshedAccount {
-                            account_name: e.account_name.clone(),
-                            refreshed: "⨯".bold().red().to_string(),
-                            expiration: e.to_string().red().to_string(),
-                        },
-                    },
-                    Err(e) => TableRefreshedAccount {
-                        account_name: "unknown".to_string(),
-                        refreshed: "⨯".bold().red().to_string(),
-                        expiration: format!("{:?}", e),
-                    },
-                })
-                .collect();
-
-            print!(
-                "\n\n{}",
-                Table::new(outputs)
-                    .with(Style::modern())
-                    .with(Modify::new(Columns::single(1)).with(Alignment::center()))
-                    .to_string()
+    // Groups with encrypted storage are written to the credential cache instead
+    let mut cache = match group.storage {
+        Storage::Plaintext => None,
+        Storage::Encrypted => {
+            Some(CredentialCache::load(&config_dir).map_err(GroupRefreshError::CredentialCache)?)
+        }
+    };
+
+    let (mut credentials_file, filepath) =
+        load_credentials_file().map_err(|e| GroupRefreshError::CredentialsFile(e.into()))?;
+
+    trace!("refresh_group.looping_through_accounts");
+
+    let mut threads: Vec<thread::JoinHandle<Result<RefreshAccountOutput, RefreshError>>> = vec![];
+    let mut reused: Vec<Result<RefreshAccountOutput, RefreshError>> = vec![];
+
+    for account in group.enabled_accounts() {
+        if let Some((credentials, valid_until)) = session.refreshed.get(&account.arn) {
+            reused.push(Ok(RefreshAccountOutput {
+                account: config::Account {
+                    valid_until: *valid_until,
+                    ..account.clone()
+                },
+                credentials: Some(credentials.clone()),
+                renewed: true,
+            }));
+            continue;
+        }
+
+        let task = AccountTask {
+            account: account.clone(),
+            session_duration: group.session_duration_for(account),
+            sts_endpoint: group.sts_endpoint_for(account),
+            min_remaining,
+            force: options.force,
+            idp_url: session.idp_url.clone(),
+            mfa_device: session.mfa_device.clone(),
+            username: session.username.clone(),
+            password: Arc::clone(&session.password),
+            mfa: Arc::clone(&session.mfa),
+            cookie_jar: session.cookie_jar.clone(),
+        };
+        let spans = logging::current_spans();
+
+        threads.push(thread::spawn(move || {
+            logging::enter_spans(spans);
+            refresh_account(task)
+        }));
+    }
+
+    let results: Vec<Result<RefreshAccountOutput, RefreshError>> = threads
+        .into_iter()
+        .map(|t| match t.join() {
+            Ok(res) => res,
+            Err(e) => Err(RefreshError::new("unknown", &format!("{:?}", e))),
+        })
+        .collect();
+
+    for output in results.iter().flatten() {
+        if let Some(ref credentials) = output.credentials {
+            session.refreshed.insert(
+                output.account.arn.clone(),
+                (credentials.clone(), output.account.valid_until),
             );
-            credentials_file.write_to_file(filepath).unwrap();
+        }
+    }
 
-            let example_account = group.accounts[0].name.clone();
+    let results: Vec<Result<AccountRefresh, RefreshError>> = results
+        .into_iter()
+        .chain(reused)
+        .map(|res| {
+            res.map(|output| {
+                let profile = group.profile_name(group_name, &output.account);
+
+                if let Some(ref credentials) = output.credentials {
+                    let region = group.region_for(&output.account);
+
+                    if let Some(ref mut cache) = cache {
+                        cache.insert(&profile, credentials, region);
+                        // Do not leave plaintext credentials of earlier refreshes behind
+                        credentials_file.delete(Some(profile.as_str()));
+                    } else {
+                        credentials_file
+                            .with_section(Some(profile.as_str()))
+                            .set("aws_access_key_id", credentials.access_key_id.as_str())
+                            .set(
+                                "aws_secret_access_key",
+                                credentials.secret_access_key.expose(),
+                            )
+                            .set("aws_session_token", credentials.session_token.expose())
+                            .set("expiration", credentials.expiration.as_str());
+
+                        if let Some(region) = region {
+                            credentials_file
+                                .with_section(Some(profile.as_str()))
+                                .set("region", region);
+                        }
+                    }
+                }
 
-            // update valid_until fields
-            for account in &mut group.accounts {
-                if let Some(valid_until) = accounts.get(&account.arn) {
-                    account.valid_until = *valid_until;
+                AccountRefresh {
+                    account: output.account,
+                    profile,
+                    credentials: output.credentials,
+                    renewed: output.renewed,
                 }
-            }
+            })
+        })
+        .collect();
 
-            println!("\nRefreshed group {}. To use them in the AWS cli, apply the --profile flag with the name of the account.", group_name.clone().yellow());
-            println!(
-                "\nExample:\n\n\taws --profile {} s3 ls\n",
-                example_account.as_str().yellow(),
-            );
+    credentials_file
+        .write_to_file(filepath)
+        .map_err(|e| GroupRefreshError::CredentialsFile(e.into()))?;
+
+    if let Some(cache) = cache {
+        cache.save().map_err(GroupRefreshError::CredentialCache)?;
+    }
+
+    // update valid_until fields
+    for account in &mut group.accounts {
+        if let Some(refresh) = results
+            .iter()
+            .flatten()
+            .find(|r| r.account.arn == account.arn)
+        {
+            account.valid_until = refresh.account.valid_until;
         }
     }
 
-    cfg.save().unwrap();
+    result.outcome = GroupOutcome::Refreshed(results);
+    Ok(result)
 }
 
 #[derive(Debug)]
@@ -254,8 +371,10 @@ struct RefreshAccountOutput {
 }
 
 #[derive(Debug)]
-struct RefreshError {
+pub struct RefreshError {
     pub account_name: String,
+    /// Kind of the error if logging in to the IdP failed
+    pub kind: Option<KeycloakErrorKind>,
     msg: String,
 }
 
@@ -263,6 +382,7 @@ impl RefreshError {
     pub fn new(account_name: &str, message: &str) -> Self {
         RefreshError {
             account_name: account_name.into(),
+            kind: None,
             msg: message.into(),
         }
     }
@@ -285,29 +405,47 @@ impl Error for RefreshError {
     }
 }
 
-fn refresh_account(
+/// Inputs of the refresh of a single account, which runs in its own thread
+struct AccountTask {
+    account: config::Account,
     session_duration: Option<i64>,
-    account: &config::Account,
-    mut cookie_jar: CookieJar,
-    idp_url: &str,
-    username: &str,
-    password: &str,
-    mfa_device: Option<&str>,
-    mfa: &str,
-    force: bool,
     sts_endpoint: Option<String>,
-) -> Result<(RefreshAccountOutput, CookieJar), RefreshError> {
-    if account.session_valid() && !force {
+    min_remaining: Duration,
+    force: bool,
+
+    // A copy of the login of the session
+    idp_url: String,
+    mfa_device: Option<String>,
+    username: String,
+    password: Arc<Secret>,
+    mfa: Arc<Secret>,
+    cookie_jar: CookieJar,
+}
+
+fn refresh_account(task: AccountTask) -> Result<RefreshAccountOutput, RefreshError> {
+    let AccountTask {
+        ref account,
+        session_duration,
+        ref sts_endpoint,
+        min_remaining,
+        force,
+        ref idp_url,
+        ref mfa_device,
+        ref username,
+        ref password,
+        ref mfa,
+        mut cookie_jar,
+    } = task;
+    let _span = logging::span(&format!("account={}", account.name));
+
+    if account.session_valid(min_remaining) && !force {
         debug!("refresh_account.session_still_valid");
 
-        return Ok((
-            RefreshAccountOutput {
-                account: account.clone(),
-                credentials: None,
-                renewed: false,
-            },
-            cookie_jar,
-        ));
+        return Ok(RefreshAccountOutput {
+            account: account.clone(),
+            credentials: None,
+            renewed: false,
+        });
     }
 
     trace!("\nrefresh_account.before_get_assertion_response",);
@@ -319,25 +457,16 @@ fn refresh_account(
         username,
         password,
         mfa_device.as_deref(),
-        &mfa.trim(),
+        mfa,
         false,
     ) {
         Ok(r) => r,
         Err(e) => {
-            println!("{} {}", account.name, "FAIL".red());
-
-            if e.kind == KeycloakErrorKind::InvalidCredentials
-                || e.kind == KeycloakErrorKind::InvalidToken
-                || e.kind == KeycloakErrorKind::PasswordUpdateRequired
-            {
-                println!(
-                    "\n{} Cannot recover from error:\n\n\t{}\n",
-                    "!".red(),
-                    e.to_string().red(),
-                );
-            }
-
-            return Err(RefreshError::new(&account.name, &e.to_string()));
+            return Err(RefreshError {
+                msg: e.to_string(),
+                kind: Some(e.kind),
+                account_name: account.name.clone(),
+            });
         }
     };
 
@@ -379,19 +508,24 @@ fn refresh_account(
     ) {
         Ok(res) => {
             trace!("refresh_account.after_assume_role.ok");
-            debug!("Access Key ID: {}", res.access_key_id);
+            debug!("credentials valid until {}", res.expiration);
 
             let mut account = account.clone();
-            account.valid_until = Some(DateTime::from_str(res.expiration.as_str()).unwrap());
+            account.valid_until = match DateTime::from_str(res.expiration.as_str()) {
+                Ok(valid_until) => Some(valid_until),
+                Err(e) => {
+                    return Err(RefreshError::new(
+                        &account.name,
+                        &format!("Invalid expiration {}: {}", res.expiration, e),
+                    ))
+                }
+            };
 
-            return Ok((
-                RefreshAccountOutput {
-                    account,
-                    credentials: Some(res),
-                    renewed: true,
-                },
-                cookie_jar,
-            ));
+            return Ok(RefreshAccountOutput {
+                account,
+                credentials: Some(res),
+                renewed: true,
+            });
         }
         Err(e) => {
             trace!("refresh_account.after_assume_role.err");
@@ -399,3 +533,105 @@ fn refresh_account(
         }
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use config::{Account, AccountSelector, Config, Group};
+
+    const ARN: &str = "arn:aws:iam::123456789012:role/Administrator";
+
+    fn session(roles: Option<Vec<RoleInfo>>) -> Session {
+        Session {
+            idp_url: "https://keycloak.example.com/auth/realms/aws".into(),
+            mfa_device: None,
+            username: "user".into(),
+            password: Arc::new(Secret::from("password".to_string())),
+            mfa: Arc::new(Secret::from("123456".to_string())),
+            cookie_jar: CookieJar::new(),
+            roles: roles.map(Ok),
+            refreshed: HashMap::new(),
+        }
+    }
+
+    fn config(accounts: Vec<Account>) -> Config {
+        let mut cfg = Config::default("config.yml");
+        cfg.groups.insert(
+            "payments".into(),
+            Group {
+                accounts,
+                ..Default::default()
+            },
+        );
+        cfg
+    }
+
+    fn account(valid_for: Duration, disabled: bool) -> Account {
+        Account {
+            name: "payments-prod".into(),
+            arn: ARN.into(),
+            valid_until: Some((Local::now() + valid_for).fixed_offset()),
+            disabled,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn refresh_group_rejects_unknown_groups() {
+        let mut cfg = config(vec![]);
+
+        match refresh_group(&mut cfg, &mut session(None), "billing", &Default::default()) {
+            Err(GroupRefreshError::UnknownGroup(name)) => assert_eq!(name, "billing"),
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn refresh_group_skips_groups_without_work() {
+        let options = RefreshOptions::default();
+
+        let mut cfg = config(vec![]);
+        let result = refresh_group(&mut cfg, &mut session(None), "payments", &options).unwrap();
+        assert!(matches!(result.outcome, GroupOutcome::Empty));
+
+        let mut cfg = config(vec![account(Duration::zero(), true)]);
+        let result = refresh_group(&mut cfg, &mut session(None), "payments", &options).unwrap();
+        assert!(matches!(result.outcome, GroupOutcome::AllDisabled));
+
+        let mut cfg = config(vec![account(Duration::hours(1), false)]);
+        let result = refresh_group(&mut cfg, &mut session(None), "payments", &options).unwrap();
+        assert!(matches!(result.outcome, GroupOutcome::Valid));
+        assert!(sessions_valid(&cfg, &options, &["payments".into()]));
+
+        let options = RefreshOptions {
+            min_remaining: Some(7200),
+            ..Default::default()
+        };
+        assert!(!sessions_valid(&cfg, &options, &["payments".into()]));
+    }
+
+    #[test]
+    fn refresh_group_reports_sync_errors() {
+        let mut cfg = config(vec![account(Duration::hours(1), false)]);
+        cfg.groups.get_mut("payments").unwrap().selectors = vec![AccountSelector {
+            prefix: Some("payments-".into()),
+            ..Default::default()
+        }];
+
+        // Without a sign-in page the account ids are used as names
+        let roles =
+            ::roles::roles_from_response(&::roles::tests::saml_response(&[ARN]), None).unwrap();
+
+        let result = refresh_group(
+            &mut cfg,
+            &mut session(Some(roles)),
+            "payments",
+            &Default::default(),
+        )
+        .unwrap();
+
+        assert!(result.sync_error.is_some());
+        assert!(result.added.is_empty() && result.removed.is_empty());
+        assert_eq!(cfg.groups["payments"].accounts.len(), 1);
+    }
+}
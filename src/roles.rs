@@ -0,0 +1,155 @@
+use std::error::Error;
+
+use cookie::CookieJar;
+
+use aws::extract_saml_accounts;
+use config;
+use keycloak::login::get_assertion_response;
+use saml::parse_assertion;
+use secret::Secret;
+
+/// A role the user is allowed to assume, as listed by `fetch_roles`
+#[derive(Debug, Clone, Serialize)]
+pub struct RoleInfo {
+    pub account_name: String,
+    pub account_id: String,
+    pub role_name: String,
+    pub arn: String,
+}
+
+/// Logs in to the IdP and returns every role the user is allowed to assume, sorted by
+/// account name. Account names are taken from the AWS sign-in page. If a role only
+/// appears in the SAML assertion, its account id is used as name instead.
+pub fn fetch_roles(
+    idp: &config::Idp,
+    username: &str,
+    password: &Secret,
+    mfa: &Secret,
+) -> Result<Vec<RoleInfo>, Box<dyn Error>> {
+    trace!("fetch_roles.get_assertion_response");
+
+    let mut cookie_jar = CookieJar::new();
+    let (saml_response, web_response) = get_assertion_response(
+        &mut cookie_jar,
+        &idp.url,
+        username,
+        password,
+        idp.mfa_device.as_deref(),
+        mfa,
+        true,
+    )?;
+
+    roles_from_response(&saml_response, web_response.as_deref())
+}
+
+/// Returns every role of the SAML response. See `fetch_roles` for how account names are
+/// resolved.
+pub fn roles_from_response(
+    saml_response: &Secret,
+    web_response: Option<&str>,
+) -> Result<Vec<RoleInfo>, Box<dyn Error>> {
+    trace!("roles_from_response.extract_saml_accounts");
+    let accounts = extract_saml_accounts(web_response.unwrap_or_default(), saml_response)?;
+
+    for account in &accounts {
+        trace!("aws_list name={} arn={}", account.name, account.arn);
+    }
+
+    trace!("roles_from_response.parse_assertion");
+    let assertion = parse_assertion(saml_response)?;
+
+    let mut roles: Vec<RoleInfo> = assertion
+        .roles
+        .into_iter()
+        .map(|role| RoleInfo {
+            account_name: accounts
+                .iter()
+                .find(|a| a.arn == role.arn)
+                .map(|a| a.name.clone())
+                .unwrap_or_else(|| role.account_id.clone()),
+            account_id: role.account_id,
+            role_name: role.role_name,
+            arn: role.arn,
+        })
+        .collect();
+
+    roles.sort_by(|a, b| (&a.account_name, &a.role_name).cmp(&(&b.account_name, &b.role_name)));
+
+    Ok(roles)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use base64::prelude::*;
+
+    /// Returns a SAML response granting the roles, as sent by the IdP
+    pub fn saml_response(arns: &[&str]) -> Secret {
+        let values: String = arns
+            .iter()
+            .map(|arn| {
+                format!(
+                    "<saml:AttributeValue>{},arn:aws:iam::{}:saml-provider/keycloak</saml:AttributeValue>",
+                    arn,
+                    &arn[13..25]
+                )
+            })
+            .collect();
+
+        Secret::from(BASE64_STANDARD.encode(format!(
+            "<samlp:Response><saml:Assertion><saml:AttributeStatement>\
+             <saml:Attribute FriendlyName=\"Role\" Name=\"Role\">{}</saml:Attribute>\
+             </saml:AttributeStatement></saml:Assertion></samlp:Response>",
+            values
+        )))
+    }
+
+    /// Returns the AWS sign-in page listing the accounts with their roles
+    pub fn sign_in_page(accounts: &[(&str, &str)]) -> String {
+        let divs: String = accounts
+            .iter()
+            .map(|(name, arn)| {
+                format!(
+                    "<div class=\"saml-account\"><div class=\"saml-account-name\">Account: {} ({})</div>\
+                     <div class=\"saml-role\"><label class=\"saml-role-description\" for=\"{}\">role</label></div></div>",
+                    name,
+                    &arn[13..25],
+                    arn
+                )
+            })
+            .collect();
+
+        format!("<html><fieldset>{}</fieldset></html>", divs)
+    }
+
+    const PROD: &str = "arn:aws:iam::123456789012:role/Administrator";
+    const DEV: &str = "arn:aws:iam::210987654321:role/ReadOnly";
+
+    #[test]
+    fn names_accounts_from_the_sign_in_page() {
+        let page = sign_in_page(&[("payments-prod", PROD), ("payments-dev", DEV)]);
+        let roles = roles_from_response(&saml_response(&[PROD, DEV]), Some(&page)).unwrap();
+
+        let names: Vec<(&str, &str)> = roles
+            .iter()
+            .map(|r| (r.account_name.as_str(), r.role_name.as_str()))
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                ("payments-dev", "ReadOnly"),
+                ("payments-prod", "Administrator")
+            ]
+        );
+        assert_eq!(roles[1].account_id, "123456789012");
+    }
+
+    #[test]
+    fn falls_back_to_account_ids() {
+        let roles = roles_from_response(&saml_response(&[PROD]), Some("<html></html>")).unwrap();
+
+        assert_eq!(roles.len(), 1);
+        assert_eq!(roles[0].account_name, "123456789012");
+        assert_eq!(roles[0].arn, PROD);
+    }
+}
@@ -1,7 +1,10 @@
+use std::error::Error;
+use std::fmt;
 use std::str::FromStr;
 
 use super::serde_xml_rs;
 use base64::prelude::*;
+use secret::Secret;
 
 #[derive(Deserialize, Debug)]
 #[serde(rename = "Response")]
@@ -57,27 +60,78 @@ pub struct Role {
     pub principal_arn: String,
 
     pub account_id: String,
-
-    #[allow(dead_code)]
     pub role_name: String,
 }
 
-pub fn parse_assertion(assertion_b64: &str) -> Result<Assertion, serde_xml_rs::Error> {
-    let mut buf: String =
-        String::from_utf8(BASE64_STANDARD.decode(&assertion_b64).unwrap()).unwrap();
+/// Why an assertion could not be parsed
+#[derive(Debug)]
+pub enum AssertionError {
+    /// The assertion is not valid base64
+    Base64(base64::DecodeError),
+    /// The decoded assertion is not valid UTF-8
+    Utf8(std::string::FromUtf8Error),
+    /// The assertion is not a SAML response
+    Xml(serde_xml_rs::Error),
+    /// An attribute of the assertion is missing a value or has an invalid one
+    InvalidAttribute(String),
+}
+
+impl fmt::Display for AssertionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            AssertionError::Base64(ref e) => write!(f, "The assertion is not valid base64: {}", e),
+            AssertionError::Utf8(ref e) => write!(f, "The assertion is not valid UTF-8: {}", e),
+            AssertionError::Xml(ref e) => write!(f, "The assertion is not a SAML response: {}", e),
+            AssertionError::InvalidAttribute(ref msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl Error for AssertionError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            AssertionError::Base64(ref e) => Some(e),
+            AssertionError::Utf8(ref e) => Some(e),
+            AssertionError::Xml(ref e) => Some(e),
+            AssertionError::InvalidAttribute(_) => None,
+        }
+    }
+}
+
+/// Returns the first value of an attribute
+fn first_value<'a>(attribute: &'a Attribute, name: &str) -> Result<&'a str, AssertionError> {
+    attribute
+        .values
+        .first()
+        .map(|v| v.value.as_str())
+        .ok_or_else(|| {
+            AssertionError::InvalidAttribute(format!("the attribute {} has no value", name))
+        })
+}
+
+/// Parses the base64 encoded SAML response of the IdP
+pub fn parse_assertion(assertion_b64: &Secret) -> Result<Assertion, AssertionError> {
+    let decoded = BASE64_STANDARD
+        .decode(assertion_b64.expose())
+        .map_err(AssertionError::Base64)?;
+    let decoded = Secret::from(String::from_utf8(decoded).map_err(AssertionError::Utf8)?);
 
     // https://github.com/RReverser/serde-xml-rs/issues/64
     // remove all namespaces (this is ugly)
-    buf = buf
-        .replace("<saml:", "<")
-        .replace("<samlp:", "<")
-        .replace("xmlns:", "")
-        .replace("xsi:", "")
-        .replace("dsig:", "")
-        .replace("</saml:", "</")
-        .replace("</samlp:", "</");
+    let buf = Secret::from(
+        decoded
+            .expose()
+            .replace("<saml:", "<")
+            .replace("<samlp:", "<")
+            .replace("xmlns:", "")
+            .replace("xsi:", "")
+            .replace("dsig:", "")
+            .replace("</saml:", "</")
+            .replace("</samlp:", "</"),
+    );
 
-    let raw_assertion: SAMLResponse = serde_xml_rs::from_str(&buf)?;
+    let raw_assertion: SAMLResponse =
+        serde_xml_rs::from_str(buf.expose()).map_err(AssertionError::Xml)?;
 
     let mut assertion = Assertion {
         role_session_name: String::new(),
@@ -88,18 +142,30 @@ pub fn parse_assertion(assertion_b64: &str) -> Result<Assertion, serde_xml_rs::E
     for attribute in &raw_assertion.assertion.attribute_statement.attributes {
         match attribute.friendly_name.as_str() {
             "RoleSessionName" => {
-                assertion.role_session_name = attribute.values.get(0).unwrap().value.clone();
+                assertion.role_session_name = first_value(attribute, "RoleSessionName")?.into();
             }
             "SessionDuration" => {
-                assertion.session_duration =
-                    i64::from_str(&attribute.values.get(0).unwrap().value.clone()).unwrap();
+                let value = first_value(attribute, "SessionDuration")?;
+                assertion.session_duration = i64::from_str(value.trim()).map_err(|_| {
+                    AssertionError::InvalidAttribute(format!("invalid session duration {}", value))
+                })?;
             }
             "Role" => {
                 for value in &attribute.values {
                     let split = value.value.split(",").collect::<Vec<&str>>();
                     let arn: String = split[0].into();
-                    let principal_arn = split[1].into();
-                    let (account_id, role_name) = arn_to_role_info(&arn);
+                    let principal_arn = match split.get(1) {
+                        Some(p) => (*p).into(),
+                        None => {
+                            return Err(AssertionError::InvalidAttribute(format!(
+                                "the role {} has no principal",
+                                arn
+                            )))
+                        }
+                    };
+                    let (account_id, role_name) = arn_to_role_info(&arn).ok_or_else(|| {
+                        AssertionError::InvalidAttribute(format!("invalid role ARN {}", arn))
+                    })?;
 
                     assertion.roles.push(Role {
                         arn,
@@ -116,11 +182,14 @@ pub fn parse_assertion(assertion_b64: &str) -> Result<Assertion, serde_xml_rs::E
     Ok(assertion)
 }
 
-// Returns the Account ID and Role Name
-fn arn_to_role_info(arn: &str) -> (String, String) {
+// Returns the Account ID and Role Name, or None if the ARN is not a role ARN
+pub fn arn_to_role_info(arn: &str) -> Option<(String, String)> {
     let split = arn.split(":").collect::<Vec<&str>>();
 
-    return (split[4].into(), split[5].to_owned().replace("role/", ""));
+    match (split.get(4), split.get(5)) {
+        (Some(account_id), Some(role)) => Some(((*account_id).into(), role.replace("role/", ""))),
+        _ => None,
+    }
 }
 
 #[cfg(test)]
@@ -131,7 +200,7 @@ mod tests {
     fn arn_to_role_info_returns_account_id() {
         let given = "arn:aws:iam::123456789012:role/ARoleName";
 
-        let (account_id, _) = arn_to_role_info(given);
+        let (account_id, _) = arn_to_role_info(given).unwrap();
 
         assert_eq!(account_id, "123456789012");
     }
@@ -140,8 +209,57 @@ mod tests {
     fn arn_to_role_info_returns_role_name() {
         let given = "arn:aws:iam::123456789012:role/ARoleName";
 
-        let (_, role_name) = arn_to_role_info(given);
+        let (_, role_name) = arn_to_role_info(given).unwrap();
 
         assert_eq!(role_name, "ARoleName");
     }
+
+    #[test]
+    fn arn_to_role_info_rejects_malformed_arns() {
+        assert!(arn_to_role_info("ARoleName").is_none());
+        assert!(arn_to_role_info("arn:aws:iam::123456789012").is_none());
+    }
+
+    fn encode(xml: &str) -> Secret {
+        Secret::from(BASE64_STANDARD.encode(xml))
+    }
+
+    #[test]
+    fn parse_assertion_rejects_invalid_input() {
+        let attribute = |name: &str, values: &str| {
+            encode(&format!(
+                "<samlp:Response><saml:Assertion><saml:AttributeStatement>\
+                 <saml:Attribute FriendlyName=\"{}\" Name=\"{}\">{}</saml:Attribute>\
+                 </saml:AttributeStatement></saml:Assertion></samlp:Response>",
+                name, name, values
+            ))
+        };
+
+        assert!(matches!(
+            parse_assertion(&Secret::from("not base64!".to_string())),
+            Err(AssertionError::Base64(_))
+        ));
+        assert!(matches!(
+            parse_assertion(&Secret::from(BASE64_STANDARD.encode([0xff, 0xfe]))),
+            Err(AssertionError::Utf8(_))
+        ));
+        assert!(matches!(
+            parse_assertion(&encode("<html></html>")),
+            Err(AssertionError::Xml(_))
+        ));
+        assert!(matches!(
+            parse_assertion(&attribute(
+                "SessionDuration",
+                "<saml:AttributeValue>an hour</saml:AttributeValue>"
+            )),
+            Err(AssertionError::InvalidAttribute(_))
+        ));
+
+        let assertion = parse_assertion(&attribute(
+            "SessionDuration",
+            "<saml:AttributeValue>7200</saml:AttributeValue>",
+        ))
+        .unwrap();
+        assert_eq!(assertion.session_duration, 7200);
+    }
 }